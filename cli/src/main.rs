@@ -1,3 +1,281 @@
-fn main() {
-    println!("Hello, world!");
+use std::{collections::BTreeMap, env, fs, process::ExitCode};
+
+use doot::{
+    Source,
+    diagnostics::{self, Diagnostic, Severity},
+    edition::Edition,
+    edits::{TextEdit, apply_edits},
+    lexer::{Lexer, builder::LexerBuilder, incremental::LexedSource, tokens::Token},
+    lints::{self, LintConfig, LintLevel},
+    source_map::{SourceFile, SourceMap},
+    span::SourceId,
+    stats::TokenStats,
+    suggest::did_you_mean,
+};
+
+const USAGE: &str = "usage: doot stats <file>
+       doot check [--format text|json] [--allow <lint>] [--warn <lint>] [--deny <lint>] <file>...
+       doot explain <code>
+       doot fix <file>...";
+
+// lexing stops at the first error, so fixing it can reveal another one
+const MAX_FIX_PASSES: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Text,
+    Json, // a single array of diagnostics, see Diagnostic::to_json
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        ["stats", path] => stats(path),
+        ["fix", ref paths @ ..] if !paths.is_empty() => fix(paths),
+        ["explain", code] => match diagnostics::explain(code) {
+            Some(explanation) => {
+                println!("{}", explanation);
+                ExitCode::SUCCESS
+            }
+            None => {
+                let codes = diagnostics::CODES.iter().map(|(code, _)| *code);
+                eprintln!("unknown code {}{}", code, suggestion(code, codes));
+                ExitCode::FAILURE
+            }
+        },
+        ["check", ref args @ ..] => match check_args(args) {
+            Ok((config, format, paths)) => check(&config, format, &paths),
+            Err(err) => {
+                eprintln!("{}\n{}", err, USAGE);
+                ExitCode::FAILURE
+            }
+        },
+        _ => {
+            eprintln!("{}", USAGE);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn check_args<'a>(args: &[&'a str]) -> Result<(LintConfig, Format, Vec<&'a str>), String> {
+    let mut config = LintConfig::new();
+    let mut format = Format::Text;
+    let mut paths = vec![];
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let level = match *arg {
+            "--format" => {
+                format = match args.next() {
+                    Some(&"text") => Format::Text,
+                    Some(&"json") => Format::Json,
+                    Some(other) => return Err(format!("unknown format {}", other)),
+                    None => return Err("missing format after --format".to_string()),
+                };
+                continue;
+            }
+            "--allow" => LintLevel::Allow,
+            "--warn" => LintLevel::Warn,
+            "--deny" => LintLevel::Deny,
+            path => {
+                paths.push(path);
+                continue;
+            }
+        };
+        let name = args
+            .next()
+            .ok_or(format!("missing lint name after {}", arg))?;
+        config = config.level(name, level);
+    }
+    if let Some(name) = config.unknown_names().first() {
+        let names = lints::LINT_NAMES.iter().copied();
+        return Err(format!("unknown lint {}{}", name, suggestion(name, names)));
+    }
+    if paths.is_empty() {
+        return Err("no files to check".to_string());
+    }
+    Ok((config, format, paths))
+}
+
+fn suggestion<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> String {
+    did_you_mean(name, candidates).map_or(String::new(), |candidate| {
+        format!(", did you mean {}?", candidate)
+    })
+}
+
+fn lex_file(path: &str) -> Result<Vec<Token>, String> {
+    let source = fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+    Lexer::new(source.chars())
+        .collect::<Result<_, _>>()
+        .map_err(|err| format!("{}: {}", path, err))
+}
+
+// lexes and lints without running, fails if any file has errors
+fn check(config: &LintConfig, format: Format, paths: &[&str]) -> ExitCode {
+    let (mut errors, mut warnings) = (0, 0);
+    let mut json = vec![];
+    let mut sources = SourceMap::new();
+    for path in paths {
+        let source = match fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(err) => {
+                match format {
+                    Format::Text => println!("{}: error: {}", path, err),
+                    Format::Json => eprintln!("{}: error: {}", path, err),
+                }
+                errors += 1;
+                continue;
+            }
+        };
+        let id = sources.add(path, &source);
+        let file = sources.get(id).unwrap();
+        for diagnostic in diagnostics(file, id, config) {
+            match diagnostic.severity() {
+                Severity::Error => errors += 1,
+                Severity::Warning => warnings += 1,
+            }
+            let file = sources.file_of(diagnostic.span()).unwrap(); // lexed with its id
+            match format {
+                Format::Text => print_diagnostic(file, &diagnostic),
+                Format::Json => json.push(diagnostic.to_json(file)),
+            }
+        }
+    }
+    match format {
+        Format::Text => println!("{} errors, {} warnings", errors, warnings),
+        Format::Json => println!("[{}]", json.join(", ")),
+    }
+    if errors > 0 {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+// the lex error, or every lint if the source lexes
+fn diagnostics(file: &SourceFile, id: SourceId, config: &LintConfig) -> Vec<Diagnostic> {
+    let lexed = LexedSource::new(file.text(), LexerBuilder::new().source_id(id));
+    if let Some((err, span)) = lexed.error() {
+        return vec![Diagnostic::from_lex_error(err, span)];
+    }
+    lints::lint(&lexed, Edition::default(), config)
+        .iter()
+        .map(Diagnostic::from_lint)
+        .collect()
+}
+
+fn print_diagnostic(file: &SourceFile, diagnostic: &Diagnostic) {
+    let path = file.name();
+    let position = file.position(diagnostic.span().start());
+    match diagnostic.code() {
+        Some(code) => println!(
+            "{}:{}: {}[{}]: {}",
+            path,
+            position,
+            diagnostic.severity(),
+            code,
+            diagnostic.message()
+        ),
+        None => println!(
+            "{}:{}: {}: {}",
+            path,
+            position,
+            diagnostic.severity(),
+            diagnostic.message()
+        ),
+    }
+    for note in diagnostic.notes() {
+        println!("  note: {}", note);
+    }
+    if diagnostic.fix().is_some() {
+        println!("  note: fixable with doot fix");
+    }
+}
+
+// applies the fixes of every diagnostic in place
+fn fix(paths: &[&str]) -> ExitCode {
+    let mut failed = false;
+    for path in paths {
+        let mut source = match fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(err) => {
+                eprintln!("{}: error: {}", path, err);
+                failed = true;
+                continue;
+            }
+        };
+        let mut applied = 0;
+        for _ in 0..MAX_FIX_PASSES {
+            let file = SourceFile::new(path, &source);
+            let mut fixes: Vec<TextEdit> =
+                diagnostics(&file, SourceId::default(), &LintConfig::new())
+                    .iter()
+                    .filter_map(|diagnostic| diagnostic.fix().cloned())
+                    .collect();
+            // overlapping fixes are left for the next pass
+            fixes.sort_by_key(|fix| fix.range().start);
+            let mut end = 0;
+            fixes.retain(|fix| {
+                let keep = fix.range().start >= end;
+                if keep {
+                    end = fix.range().end;
+                }
+                keep
+            });
+            if fixes.is_empty() {
+                break;
+            }
+            match apply_edits(&source, &fixes) {
+                Ok(fixed) => source = fixed,
+                Err(err) => {
+                    eprintln!("{}: error: {}", path, err);
+                    failed = true;
+                    break;
+                }
+            }
+            applied += fixes.len();
+        }
+        if applied > 0
+            && let Err(err) = fs::write(path, &source)
+        {
+            eprintln!("{}: error: {}", path, err);
+            failed = true;
+            continue;
+        }
+        println!("{}: {} fixes applied", path, applied);
+    }
+    if failed {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+// printed as json
+fn stats(path: &str) -> ExitCode {
+    let tokens = match lex_file(path) {
+        Ok(tokens) => tokens,
+        Err(err) => {
+            eprintln!("{}", err);
+            return ExitCode::FAILURE;
+        }
+    };
+    let stats = TokenStats::from_tokens(&tokens);
+    println!(
+        "{{\"kinds\": {}, \"identifiers\": {}, \"statements\": {}, \"average_statement_length\": {}, \"max_nesting\": {}}}",
+        json_counts(stats.kinds()),
+        json_counts(stats.identifiers()),
+        stats.statements(),
+        stats.average_statement_length(),
+        stats.max_nesting()
+    );
+    ExitCode::SUCCESS
+}
+
+// keys are token kinds and identifiers, which never need escaping
+fn json_counts(counts: &BTreeMap<String, usize>) -> String {
+    let entries: Vec<_> = counts
+        .iter()
+        .map(|(key, count)| format!("\"{}\": {}", key, count))
+        .collect();
+    format!("{{{}}}", entries.join(", "))
 }
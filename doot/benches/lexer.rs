@@ -0,0 +1,44 @@
+use criterion::{Criterion, black_box, criterion_group, criterion_main};
+use doot::lexer::Lexer;
+
+const SOURCE: &str = r#"
+// a small program touching every lexer state
+class Point {
+    fn new(x, y) {
+        return { x: x, y: y };
+    }
+}
+
+/* block
+   comment */
+fn main() {
+    let values = [1, 2.5, 0xFF, 1_000e3];
+    var text = "sum: ${values[0] + values[1]}\n";
+    const raw = #`raw ${not} interpolated`#;
+    let doc = <<~END
+        heredoc text
+        END;
+    for value in values {
+        if value >= 2 && not false {
+            text += "${value}, ";
+        } else {
+            text -= null;
+        }
+    }
+    while true { return text; }
+}
+"#;
+
+fn lex(c: &mut Criterion) {
+    let source = SOURCE.repeat(20);
+    c.bench_function("lex", |b| {
+        b.iter(|| {
+            Lexer::new(black_box(source.as_str()).chars())
+                .map(Result::unwrap)
+                .count()
+        })
+    });
+}
+
+criterion_group!(benches, lex);
+criterion_main!(benches);
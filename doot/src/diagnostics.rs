@@ -0,0 +1,474 @@
+use std::fmt::Display;
+
+use crate::{
+    Source,
+    edits::TextEdit,
+    lexer::TokenizationError,
+    lints::{Lint, LintLevel},
+    source_map::SourceFile,
+    span::Span,
+};
+
+// every code a diagnostic can have with its explanation, lints use their name as code.
+// codes are never reused, even if the diagnostic is removed
+pub const CODES: &[(&str, &str)] = &[
+    (
+        "E0001",
+        "A character that does not start any token.
+
+    let a = 1 @ 2;
+
+Remove the character, or put it in a string or a comment. Embedders can
+lex it as Token::Unknown with LexerBuilder::unknown_tokens.",
+    ),
+    (
+        "E0002",
+        "An identifier longer than the limit set with
+LexerBuilder::max_identifier_length. The length is counted in chars,
+after normalizing the identifier to NFC.",
+    ),
+    (
+        "E0003",
+        "A backslash in a string followed by whitespace or the end of the
+source.
+
+    \"a\\ b\"
+
+Write \\\\ for a backslash.",
+    ),
+    (
+        "E0004",
+        "An unknown escape in a string.
+
+    \"\\q\"
+
+The escapes are \\n, \\r, \\t, \\\\, \\0, \\$, \\\", \\', \\xNN and \\u{NNNN}.",
+    ),
+    (
+        "E0005",
+        "A \\x or \\u escape without its value.
+
+    \"\\u\"
+    \"\\x4\"
+
+\\x takes exactly two hex digits and \\u takes hex digits in braces, as in
+\\x41 and \\u{41}.",
+    ),
+    (
+        "E0006",
+        "A \\x escape whose two characters are not hex digits.
+
+    \"\\xg1\"",
+    ),
+    (
+        "E0007",
+        "A \\x escape above 7F. \\x only writes ascii characters, use \\u for
+the others.
+
+    \"\\xFF\"   // error
+    \"\\u{FF}\" // ÿ",
+    ),
+    (
+        "E0008",
+        "A \\u escape whose value is not a hex number.
+
+    \"\\u{g}\"",
+    ),
+    (
+        "E0009",
+        "A \\u escape whose value is not a unicode scalar value, like a
+surrogate or a value above 10FFFF.
+
+    \"\\u{D800}\"",
+    ),
+    (
+        "E0010",
+        "A number literal that cannot be parsed. Ints must fit in 64 bits without
+their sign and only take the radix prefixes 0x, 0b and 0o, which floats
+cannot have.
+
+    0a12
+    1.5e
+    0x1.5
+    18446744073709551616",
+    ),
+    (
+        "E0011",
+        "A NUL character in the source. Sources are text, write \\0 in a string
+instead.",
+    ),
+    (
+        "E0012",
+        "A # that does not open a raw string, which starts with one or more #
+followed by a backtick.
+
+    let a = #`raw`#; // ok
+    let b = #;       // error",
+    ),
+    (
+        "E0013",
+        "A raw string opened with more # than allowed, 255 unless set with
+LexerBuilder::max_raw_string_pounds.",
+    ),
+    (
+        "E0014",
+        "An internal lexer error, which is a bug in doot. Please report it with
+the source that caused it.",
+    ),
+    (
+        "E0015",
+        "A heredoc opener that does not end the line. The delimiter after <<~
+starts with a letter or _ and can only be followed by spaces or tabs.
+
+    let a = <<~END
+      text
+      END;          // ok
+    let b = <<~END; // error",
+    ),
+    (
+        "empty_interpolation",
+        "An interpolation with nothing in it, which adds nothing to the string.
+
+    \"a${}b\"
+
+The fix removes it.",
+    ),
+    (
+        "raw_string_interpolation",
+        "${ in a raw string, which is kept as text since raw strings are never
+interpolated.
+
+    #`total: ${count}`#
+
+The fix turns the raw string into a quoted string.",
+    ),
+    (
+        "unterminated_interpolation",
+        "An interpolation that is never closed, so the rest of the string is
+lexed as code.
+
+    \"cost: ${\"
+
+The fix escapes it as \\${ to keep it as text.",
+    ),
+    (
+        "reserved_word",
+        "An identifier that a later edition may turn into a keyword.
+
+    let match = 1;
+
+Rename it, or write it as a raw identifier like r#match.",
+    ),
+];
+
+// codes are case insensitive, and lint names can be written with - instead of _
+pub fn explain(code: &str) -> Option<&'static str> {
+    let code = code.to_uppercase().replace('-', "_");
+    CODES
+        .iter()
+        .find(|(known, _)| known.to_uppercase() == code)
+        .map(|(_, explanation)| *explanation)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        }
+        .fmt(f)
+    }
+}
+
+// a problem found in a source, by any stage
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    code: Option<&'static str>, // one of CODES
+    severity: Severity,
+    span: Span,
+    message: String,
+    notes: Vec<String>,
+    fix: Option<TextEdit>,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, span: Span, message: &str) -> Self {
+        Self {
+            code: None,
+            severity,
+            span,
+            message: message.to_string(),
+            notes: vec![],
+            fix: None,
+        }
+    }
+
+    // span is used if the error does not know its own, usually Lexer::span after the error
+    pub fn from_lex_error(err: &TokenizationError, span: Span) -> Self {
+        let span = err
+            .span()
+            .map_or(span, |range| Span::new(span.source_id(), range));
+        Self {
+            fix: err.fix(),
+            ..Self::new(Severity::Error, span, &err.to_string()).with_code(err.code())
+        }
+    }
+
+    pub fn from_lint(lint: &Lint) -> Self {
+        let severity = match lint.level() {
+            LintLevel::Deny => Severity::Error,
+            _ => Severity::Warning,
+        };
+        Self {
+            fix: lint.fix().cloned(),
+            ..Self::new(severity, *lint.span(), lint.message()).with_code(lint.name())
+        }
+    }
+
+    pub fn with_code(mut self, code: &'static str) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    pub fn with_note(mut self, note: &str) -> Self {
+        self.notes.push(note.to_string());
+        self
+    }
+
+    pub fn with_fix(mut self, fix: TextEdit) -> Self {
+        self.fix = Some(fix);
+        self
+    }
+
+    pub fn code(&self) -> Option<&'static str> {
+        self.code
+    }
+
+    pub fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    pub fn span(&self) -> &Span {
+        &self.span
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn notes(&self) -> &[String] {
+        &self.notes
+    }
+
+    pub fn fix(&self) -> Option<&TextEdit> {
+        self.fix.as_ref()
+    }
+
+    // a single json object, file is the source the span points into.
+    // lines and columns start at 1 and count chars, offsets are in bytes
+    pub fn to_json(&self, file: &SourceFile) -> String {
+        let position = |offset| {
+            let position = file.position(offset);
+            format!(
+                "{{\"offset\": {}, \"line\": {}, \"col\": {}}}",
+                offset,
+                position.line(),
+                position.col()
+            )
+        };
+        let notes: Vec<_> = self.notes.iter().map(|note| json_string(note)).collect();
+        let fix = self.fix.as_ref().map_or("null".to_string(), |fix| {
+            format!(
+                "{{\"start\": {}, \"end\": {}, \"text\": {}}}",
+                position(fix.range().start),
+                position(fix.range().end),
+                json_string(fix.new_text())
+            )
+        });
+        format!(
+            "{{\"file\": {}, \"code\": {}, \"severity\": \"{}\", \"message\": {}, \"start\": {}, \"end\": {}, \"notes\": [{}], \"fix\": {}}}",
+            json_string(file.name()),
+            self.code.map_or("null".to_string(), json_string),
+            self.severity,
+            json_string(&self.message),
+            position(self.span.start()),
+            position(self.span.end()),
+            notes.join(", "),
+            fix
+        )
+    }
+}
+
+fn json_string(value: &str) -> String {
+    let mut json = String::from("\"");
+    for ch in value.chars() {
+        match ch {
+            '"' => json.push_str(r#"\""#),
+            '\\' => json.push_str(r"\\"),
+            '\n' => json.push_str(r"\n"),
+            '\r' => json.push_str(r"\r"),
+            '\t' => json.push_str(r"\t"),
+            ch if ch.is_control() => json.push_str(&format!("\\u{:04x}", ch as u32)),
+            ch => json.push(ch),
+        }
+    }
+    json.push('"');
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use std::slice;
+
+    use rstest::rstest;
+
+    use crate::{
+        edition::Edition,
+        edits::{TextEdit, apply_edits},
+        lexer::{Lexer, builder::LexerBuilder, incremental::LexedSource},
+        lints::{LINT_NAMES, LintConfig, LintLevel, lint},
+        source_map::SourceFile,
+        span::{SourceId, SourcePosition, Span},
+    };
+
+    use super::{CODES, Diagnostic, Severity, explain, json_string};
+
+    #[rstest]
+    fn from_lex_error() {
+        let mut lexer = Lexer::new(r#"a "b\q""#.chars());
+        let err = lexer.by_ref().find_map(Result::err).unwrap();
+        let diagnostic = Diagnostic::from_lex_error(&err, lexer.span());
+        assert_eq!(Severity::Error, diagnostic.severity());
+        assert_eq!(4..6, diagnostic.span().range()); // the escape, not the lexer span
+        assert_eq!(r"invalid escape \q", diagnostic.message());
+        assert_eq!(Some("E0004"), diagnostic.code());
+    }
+
+    #[rstest]
+    fn nul_from_lex_error() {
+        let source = "let a = 1;\n\0";
+        let mut lexer = Lexer::new(source.chars());
+        let err = lexer.by_ref().find_map(Result::err).unwrap();
+        let diagnostic = Diagnostic::from_lex_error(&err, lexer.span());
+        assert_eq!(11..12, diagnostic.span().range()); // the NUL, not the last token
+        assert_eq!("unexpected NUL character", diagnostic.message());
+        let position = SourceFile::new("a.dt", source).position(diagnostic.span().start());
+        assert_eq!(SourcePosition::new(2, 1), position);
+    }
+
+    #[rstest]
+    #[case(r#""a\q""#, Some(r#""a\\q""#))]
+    #[case(r#""\ ""#, Some(r#""\\ ""#))]
+    #[case(r#""\xFF""#, Some(r#""\u{FF}""#))]
+    #[case(r#""\xg1""#, None)]
+    #[case("@", None)]
+    fn lex_error_fixes(#[case] source: &str, #[case] expected: Option<&str>) {
+        let mut lexer = Lexer::new(source.chars());
+        let err = lexer.by_ref().find_map(Result::err).unwrap();
+        let diagnostic = Diagnostic::from_lex_error(&err, lexer.span());
+        let fixed = diagnostic
+            .fix()
+            .map(|fix| apply_edits(source, slice::from_ref(fix)).unwrap());
+        assert_eq!(expected.map(str::to_string), fixed);
+    }
+
+    #[rstest]
+    fn codes_are_unique() {
+        let mut codes: Vec<_> = CODES.iter().map(|(code, _)| code.to_uppercase()).collect();
+        codes.sort();
+        codes.dedup();
+        assert_eq!(CODES.len(), codes.len());
+    }
+
+    #[rstest]
+    #[case("@")]
+    #[case("\"\\ \"")]
+    #[case("\"\\q\"")]
+    #[case("\"\\u\"")]
+    #[case("\"\\xg1\"")]
+    #[case("\"\\xFF\"")]
+    #[case("\"\\u{g}\"")]
+    #[case("\"\\u{D800}\"")]
+    #[case("0a12")]
+    #[case("\0")]
+    #[case("#")]
+    fn lex_errors_are_explained(#[case] source: &str) {
+        let err = Lexer::new(source.chars()).find_map(Result::err).unwrap();
+        assert!(
+            explain(err.code()).is_some(),
+            "{} is not explained",
+            err.code()
+        );
+    }
+
+    #[rstest]
+    fn lints_are_explained() {
+        for name in LINT_NAMES {
+            assert!(explain(name).is_some(), "{} is not explained", name);
+        }
+    }
+
+    #[rstest]
+    #[case("E0004", true)]
+    #[case("e0004", true)]
+    #[case("reserved-word", true)]
+    #[case("E9999", false)]
+    fn explained(#[case] code: &str, #[case] expected: bool) {
+        assert_eq!(expected, explain(code).is_some());
+    }
+
+    #[rstest]
+    fn from_lint() {
+        let lexed = LexedSource::new(r#""${}" enum"#, LexerBuilder::new());
+        let config = LintConfig::new().level("reserved_word", LintLevel::Deny);
+        let diagnostics: Vec<_> = lint(&lexed, Edition::Edition2025, &config)
+            .iter()
+            .map(Diagnostic::from_lint)
+            .map(|diagnostic| (diagnostic.code(), diagnostic.severity()))
+            .collect();
+        assert_eq!(
+            vec![
+                (Some("empty_interpolation"), Severity::Warning),
+                (Some("reserved_word"), Severity::Error),
+            ],
+            diagnostics
+        );
+    }
+
+    #[rstest]
+    fn to_json() {
+        let text = "let a;\nlet é = \"";
+        let diagnostic = Diagnostic::new(
+            Severity::Warning,
+            Span::new(SourceId(0), 11..15),
+            "something \"quoted\"",
+        )
+        .with_code("some_lint")
+        .with_note("first")
+        .with_note("second")
+        .with_fix(TextEdit::new(11..13, "e"));
+        assert_eq!(
+            r#"{"file": "dir\\a.dt", "code": "some_lint", "severity": "warning", "message": "something \"quoted\"", "start": {"offset": 11, "line": 2, "col": 5}, "end": {"offset": 15, "line": 2, "col": 8}, "notes": ["first", "second"], "fix": {"start": {"offset": 11, "line": 2, "col": 5}, "end": {"offset": 13, "line": 2, "col": 6}, "text": "e"}}"#,
+            diagnostic.to_json(&SourceFile::new(r"dir\a.dt", text))
+        );
+        let diagnostic = Diagnostic::new(Severity::Error, Span::new(SourceId(0), 0..0), "");
+        assert_eq!(
+            r#"{"file": "a.dt", "code": null, "severity": "error", "message": "", "start": {"offset": 0, "line": 1, "col": 1}, "end": {"offset": 0, "line": 1, "col": 1}, "notes": [], "fix": null}"#,
+            diagnostic.to_json(&SourceFile::new("a.dt", text))
+        );
+    }
+
+    #[rstest]
+    #[case("a", r#""a""#)]
+    #[case("a\"b\\", r#""a\"b\\""#)]
+    #[case("\n\t\u{1}é", r#""\n\t\u0001é""#)]
+    fn json_strings(#[case] value: &str, #[case] expected: &str) {
+        assert_eq!(expected, json_string(value));
+    }
+}
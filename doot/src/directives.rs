@@ -0,0 +1,297 @@
+use std::{error::Error, fmt::Display};
+
+use crate::{
+    lexer::{incremental::LexedToken, tokens::Token},
+    source_map::SourceFile,
+    span::Span,
+};
+
+const PREFIX: &str = "doot-";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Directive {
+    Format(bool),        // doot-fmt: on, doot-fmt: off
+    Allow(Vec<String>),  // doot-allow(unused, ...)
+    Ignore(Vec<String>), // doot-ignore: unused, ... (only the next line)
+    CoverageIgnore,      // doot-coverage: ignore
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DirectiveParseError {
+    UnknownDirective(String),
+    InvalidValue(String, String),
+    Malformed(String),
+}
+
+impl Error for DirectiveParseError {}
+impl Display for DirectiveParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DirectiveParseError::UnknownDirective(name) => {
+                format!("unknown directive {}{}", PREFIX, name)
+            }
+            DirectiveParseError::InvalidValue(name, value) => {
+                format!("invalid value {} for directive {}{}", value, PREFIX, name)
+            }
+            DirectiveParseError::Malformed(source) => format!("malformed directive {}", source),
+        }
+        .fmt(f)
+    }
+}
+
+impl Directive {
+    // None if the comment is not a directive at all
+    pub fn parse(comment: &str) -> Option<Result<Self, DirectiveParseError>> {
+        let source = comment.trim().strip_prefix(PREFIX)?;
+        Some(Self::parse_body(source).unwrap_or_else(|| {
+            Err(DirectiveParseError::Malformed(format!(
+                "{}{}",
+                PREFIX,
+                source.trim_end()
+            )))
+        }))
+    }
+
+    fn parse_body(source: &str) -> Option<Result<Self, DirectiveParseError>> {
+        let split = source.find([':', '('])?;
+        let (name, rest) = source.split_at(split);
+        let name = name.trim();
+        if name.is_empty() {
+            return None;
+        }
+        let args: Vec<_> = match rest.strip_prefix(':') {
+            Some(value) if name == "ignore" => value.split(',').collect(),
+            Some(value) => vec![value],
+            None => rest
+                .strip_prefix('(')?
+                .trim_end()
+                .strip_suffix(')')?
+                .split(',')
+                .collect(),
+        };
+        let args: Vec<_> = args.iter().map(|arg| arg.trim().to_string()).collect();
+        if args.iter().any(String::is_empty) {
+            return None;
+        }
+        let invalid_value = || {
+            Err(DirectiveParseError::InvalidValue(
+                name.to_string(),
+                args.join(", "),
+            ))
+        };
+        Some(match (name, rest.starts_with(':')) {
+            ("fmt", true) => match args[0].as_str() {
+                "on" => Ok(Self::Format(true)),
+                "off" => Ok(Self::Format(false)),
+                _ => invalid_value(),
+            },
+            ("allow", false) => Ok(Self::Allow(args)),
+            ("ignore", true) => Ok(Self::Ignore(args)),
+            ("coverage", true) => match args[0].as_str() {
+                "ignore" => Ok(Self::CoverageIgnore),
+                _ => invalid_value(),
+            },
+            ("fmt" | "allow" | "ignore" | "coverage", _) => return None,
+            _ => Err(DirectiveParseError::UnknownDirective(name.to_string())),
+        })
+    }
+}
+
+// directives found in the comments of a lexed source, with the span of their comment
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DirectiveTable {
+    entries: Vec<(Span, Directive)>,
+    errors: Vec<(Span, DirectiveParseError)>,
+}
+
+impl DirectiveTable {
+    pub fn from_tokens<'a>(tokens: impl IntoIterator<Item = &'a LexedToken>) -> Self {
+        let mut table = Self::default();
+        for token in tokens {
+            let comment = match token.token() {
+                Token::CommentLiteral(text) | Token::Comment { text, .. } => text,
+                _ => continue,
+            };
+            match Directive::parse(comment) {
+                Some(Ok(directive)) => table.entries.push((token.span(), directive)),
+                Some(Err(err)) => table.errors.push((token.span(), err)),
+                None => {}
+            }
+        }
+        table
+    }
+
+    pub fn entries(&self) -> &[(Span, Directive)] {
+        &self.entries
+    }
+
+    pub fn errors(&self) -> &[(Span, DirectiveParseError)] {
+        &self.errors
+    }
+
+    pub fn format_enabled(&self, offset: usize) -> bool {
+        self.before(offset)
+            .filter_map(|d| match d {
+                Directive::Format(enabled) => Some(*enabled),
+                _ => None,
+            })
+            .last()
+            .unwrap_or(true)
+    }
+
+    // doot-allow applies to the rest of the source
+    pub fn allowed(&self, lint: &str, offset: usize) -> bool {
+        self.before(offset).any(|d| match d {
+            Directive::Allow(names) => names_contain(names, lint),
+            _ => false,
+        })
+    }
+
+    // doot-ignore applies to the line after its comment, file is the source of the tokens
+    pub fn ignored(&self, lint: &str, offset: usize, file: &SourceFile) -> bool {
+        let line = file.position(offset).line();
+        self.entries.iter().any(|(span, d)| match d {
+            Directive::Ignore(names) => {
+                file.position(span.start()).line() + 1 == line && names_contain(names, lint)
+            }
+            _ => false,
+        })
+    }
+
+    fn before(&self, offset: usize) -> impl Iterator<Item = &Directive> {
+        self.entries
+            .iter()
+            .take_while(move |(span, _)| span.end() <= offset)
+            .map(|(_, d)| d)
+    }
+}
+
+// names can be written with - instead of _
+fn names_contain(names: &[String], lint: &str) -> bool {
+    names.iter().any(|name| name.replace('-', "_") == lint)
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use crate::{
+        lexer::{builder::LexerBuilder, incremental::LexedSource},
+        source_map::SourceFile,
+    };
+
+    use super::{Directive, DirectiveParseError, DirectiveTable};
+
+    #[rstest]
+    #[case(" doot-fmt: off", Directive::Format(false))]
+    #[case("doot-fmt:on ", Directive::Format(true))]
+    #[case(" doot-allow(unused)", Directive::Allow(vec!["unused".to_string()]))]
+    #[case(
+        " doot-allow(unused, shadowing )",
+        Directive::Allow(vec!["unused".to_string(), "shadowing".to_string()])
+    )]
+    #[case(
+        " doot-ignore: reserved-word, empty_interpolation",
+        Directive::Ignore(vec!["reserved-word".to_string(), "empty_interpolation".to_string()])
+    )]
+    #[case(" doot-coverage: ignore", Directive::CoverageIgnore)]
+    fn parse_ok(#[case] source: &str, #[case] expected: Directive) {
+        assert_eq!(Some(Ok(expected)), Directive::parse(source));
+    }
+
+    #[rstest]
+    #[case(" foo")]
+    #[case(" doot is fun")]
+    #[case("")]
+    fn not_directive(#[case] source: &str) {
+        assert_eq!(None, Directive::parse(source));
+    }
+
+    #[rstest]
+    #[case(" doot-foo: bar", DirectiveParseError::UnknownDirective("foo".to_string()))]
+    #[case(
+        " doot-fmt: maybe",
+        DirectiveParseError::InvalidValue("fmt".to_string(), "maybe".to_string())
+    )]
+    #[case(
+        " doot-coverage: skip",
+        DirectiveParseError::InvalidValue("coverage".to_string(), "skip".to_string())
+    )]
+    #[case(" doot-fmt", DirectiveParseError::Malformed("doot-fmt".to_string()))]
+    #[case(" doot-fmt:", DirectiveParseError::Malformed("doot-fmt:".to_string()))]
+    #[case(" doot-allow()", DirectiveParseError::Malformed("doot-allow()".to_string()))]
+    #[case(" doot-allow(unused", DirectiveParseError::Malformed("doot-allow(unused".to_string()))]
+    #[case(" doot-allow: unused", DirectiveParseError::Malformed("doot-allow: unused".to_string()))]
+    #[case(" doot-ignore(unused)", DirectiveParseError::Malformed("doot-ignore(unused)".to_string()))]
+    #[case(" doot-ignore: a,", DirectiveParseError::Malformed("doot-ignore: a,".to_string()))]
+    fn parse_fail(#[case] source: &str, #[case] expected: DirectiveParseError) {
+        assert_eq!(Some(Err(expected)), Directive::parse(source));
+    }
+
+    #[rstest]
+    #[case(false)]
+    #[case(true)]
+    fn table_from_lexer(#[case] merge_comments: bool) {
+        let source = "// doot-fmt: off\nlet a = 1\n/* doot-fmt: on */ // doot-foo: bar\n// plain";
+        let lexed = LexedSource::new(source, LexerBuilder::new().merge_comments(merge_comments));
+        let table = DirectiveTable::from_tokens(lexed.tokens());
+        let entries: Vec<_> = table
+            .entries()
+            .iter()
+            .map(|(span, d)| (&source[span.range()], d.clone()))
+            .collect();
+        let (off, on, foo) = if merge_comments {
+            (
+                "// doot-fmt: off\n",
+                "/* doot-fmt: on */",
+                "// doot-foo: bar\n",
+            )
+        } else {
+            (" doot-fmt: off", " doot-fmt: on ", " doot-foo: bar")
+        };
+        assert_eq!(
+            vec![
+                (off, Directive::Format(false)),
+                (on, Directive::Format(true))
+            ],
+            entries
+        );
+        let errors: Vec<_> = table
+            .errors()
+            .iter()
+            .map(|(span, err)| (&source[span.range()], err.clone()))
+            .collect();
+        assert_eq!(
+            vec![(
+                foo,
+                DirectiveParseError::UnknownDirective("foo".to_string())
+            )],
+            errors
+        );
+        assert!(table.format_enabled(0));
+        assert!(!table.format_enabled(17));
+        assert!(table.format_enabled(45));
+    }
+
+    #[rstest]
+    fn allowed() {
+        let source = "a // doot-allow(reserved-word, other)\nb";
+        let lexed = LexedSource::new(source, LexerBuilder::new());
+        let table = DirectiveTable::from_tokens(lexed.tokens());
+        assert!(!table.allowed("reserved_word", 0));
+        assert!(table.allowed("reserved_word", 38));
+        assert!(table.allowed("other", 38));
+        assert!(!table.allowed("empty_interpolation", 38));
+    }
+
+    #[rstest]
+    fn ignored() {
+        let source = "// doot-ignore: reserved-word\na\nb";
+        let lexed = LexedSource::new(source, LexerBuilder::new());
+        let table = DirectiveTable::from_tokens(lexed.tokens());
+        let file = SourceFile::new("a.dt", source);
+        assert!(table.ignored("reserved_word", 30, &file));
+        assert!(!table.ignored("reserved_word", 32, &file)); // only the next line
+        assert!(!table.ignored("empty_interpolation", 30, &file));
+    }
+}
@@ -0,0 +1,18 @@
+// language editions, gating keywords that future versions may add
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Edition {
+    #[default]
+    Edition2025,
+}
+
+impl Edition {
+    // lexed as identifiers, but warned about since a later edition may turn them into keywords
+    pub fn reserved_words(&self) -> &'static [&'static str] {
+        match self {
+            Edition::Edition2025 => &[
+                "match", "enum", "import", "export", "async", "await", "yield", "trait", "type",
+                "loop", "break", "continue",
+            ],
+        }
+    }
+}
@@ -0,0 +1,201 @@
+use std::{collections::BTreeMap, error::Error, fmt::Display, ops::Range};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    range: Range<usize>, // byte offsets in the original text
+    new_text: String,
+}
+
+impl TextEdit {
+    pub fn new(range: Range<usize>, new_text: &str) -> Self {
+        Self {
+            range,
+            new_text: new_text.to_string(),
+        }
+    }
+
+    pub fn insert(offset: usize, text: &str) -> Self {
+        Self::new(offset..offset, text)
+    }
+
+    pub fn delete(range: Range<usize>) -> Self {
+        Self::new(range, "")
+    }
+
+    pub fn range(&self) -> &Range<usize> {
+        &self.range
+    }
+
+    pub fn new_text(&self) -> &str {
+        &self.new_text
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EditError {
+    InvalidRange(Range<usize>),
+    OutOfBounds(Range<usize>),
+    NotCharBoundary(usize),
+    Overlapping(Range<usize>, Range<usize>),
+}
+
+impl Error for EditError {}
+impl Display for EditError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EditError::InvalidRange(range) => format!("invalid edit range {:?}", range),
+            EditError::OutOfBounds(range) => format!("edit range {:?} out of bounds", range),
+            EditError::NotCharBoundary(offset) => {
+                format!("edit offset {} is not a char boundary", offset)
+            }
+            EditError::Overlapping(first, second) => {
+                format!("overlapping edit ranges {:?} and {:?}", first, second)
+            }
+        }
+        .fmt(f)
+    }
+}
+
+pub fn apply_edits(source: &str, edits: &[TextEdit]) -> Result<String, EditError> {
+    let mut sorted: Vec<_> = edits.iter().collect();
+    sorted.sort_by_key(|e| (e.range.start, e.range.end)); // stable, inserts keep their order
+    for edit in &sorted {
+        let range = &edit.range;
+        if range.start > range.end {
+            return Err(EditError::InvalidRange(range.clone()));
+        }
+        if range.end > source.len() {
+            return Err(EditError::OutOfBounds(range.clone()));
+        }
+        if let Some(offset) = [range.start, range.end]
+            .into_iter()
+            .find(|o| !source.is_char_boundary(*o))
+        {
+            return Err(EditError::NotCharBoundary(offset));
+        }
+    }
+    if let Some(pair) = sorted
+        .windows(2)
+        .find(|pair| pair[0].range.end > pair[1].range.start)
+    {
+        return Err(EditError::Overlapping(
+            pair[0].range.clone(),
+            pair[1].range.clone(),
+        ));
+    }
+    let mut result = String::with_capacity(source.len());
+    let mut last = 0;
+    for edit in sorted {
+        result.push_str(&source[last..edit.range.start]);
+        result.push_str(&edit.new_text);
+        last = edit.range.end;
+    }
+    result.push_str(&source[last..]);
+    Ok(result)
+}
+
+// edits grouped by source name
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct WorkspaceEdit {
+    changes: BTreeMap<String, Vec<TextEdit>>,
+}
+
+impl WorkspaceEdit {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, source_name: &str, edit: TextEdit) {
+        self.changes
+            .entry(source_name.to_string())
+            .or_default()
+            .push(edit);
+    }
+
+    pub fn sources(&self) -> impl Iterator<Item = &str> {
+        self.changes.keys().map(String::as_str)
+    }
+
+    pub fn edits(&self, source_name: &str) -> &[TextEdit] {
+        self.changes
+            .get(source_name)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    pub fn apply(&self, source_name: &str, source: &str) -> Result<String, EditError> {
+        apply_edits(source, self.edits(source_name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::{EditError, TextEdit, WorkspaceEdit, apply_edits};
+
+    #[rstest]
+    #[case("let a = 1", [], "let a = 1")]
+    #[case("let a = 1", [TextEdit::new(4..5, "foo")], "let foo = 1")]
+    #[case("let a = 1", [TextEdit::insert(9, ";")], "let a = 1;")]
+    #[case("let a = 1", [TextEdit::delete(0..4)], "a = 1")]
+    #[case(
+        "let a = 1",
+        [TextEdit::new(8..9, "2"), TextEdit::new(0..3, "var")],
+        "var a = 2"
+    )] // unsorted
+    #[case(
+        "ab",
+        [TextEdit::insert(1, "x"), TextEdit::insert(1, "y"), TextEdit::new(1..2, "c")],
+        "axyc"
+    )] // inserts at the same offset keep their order
+    #[case("é€", [TextEdit::new(2..5, "e")], "ée")] // multibyte
+    fn apply_ok<const N: usize>(
+        #[case] source: &str,
+        #[case] edits: [TextEdit; N],
+        #[case] expected: &str,
+    ) {
+        assert_eq!(Ok(expected.to_string()), apply_edits(source, &edits));
+    }
+
+    #[rstest]
+    #[case("abc", [TextEdit::new(2..1, "")], EditError::InvalidRange(2..1))]
+    #[case("abc", [TextEdit::new(2..4, "")], EditError::OutOfBounds(2..4))]
+    #[case("é", [TextEdit::insert(1, "")], EditError::NotCharBoundary(1))]
+    #[case(
+        "abcdef",
+        [TextEdit::new(3..5, ""), TextEdit::new(1..4, "")],
+        EditError::Overlapping(1..4, 3..5)
+    )]
+    #[case(
+        "abcdef",
+        [TextEdit::new(1..4, ""), TextEdit::insert(2, "x")],
+        EditError::Overlapping(1..4, 2..2)
+    )]
+    #[allow(clippy::reversed_empty_ranges)]
+    fn apply_fail<const N: usize>(
+        #[case] source: &str,
+        #[case] edits: [TextEdit; N],
+        #[case] expected: EditError,
+    ) {
+        assert_eq!(Err(expected), apply_edits(source, &edits));
+    }
+
+    #[rstest]
+    fn workspace_edit() {
+        let mut edit = WorkspaceEdit::new();
+        edit.add("main.doot", TextEdit::new(0..3, "var"));
+        edit.add("lib.doot", TextEdit::insert(0, "// lib\n"));
+        edit.add("main.doot", TextEdit::insert(9, ";"));
+        assert_eq!(
+            vec!["lib.doot", "main.doot"],
+            edit.sources().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            Ok("var a = 1;".to_string()),
+            edit.apply("main.doot", "let a = 1")
+        );
+        assert_eq!(Ok("// lib\nfoo".to_string()), edit.apply("lib.doot", "foo"));
+        assert_eq!(Ok("foo".to_string()), edit.apply("other.doot", "foo"));
+    }
+}
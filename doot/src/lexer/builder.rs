@@ -0,0 +1,398 @@
+use crate::span::SourceId;
+
+use super::Lexer;
+
+#[derive(Debug, Clone)]
+pub(super) struct LexerOptions {
+    pub(super) case_insensitive_keywords: bool,
+    pub(super) extra_keywords: Vec<String>,
+    pub(super) line_comment: String,
+    pub(super) block_comment: (String, String),
+    pub(super) unknown_tokens: bool,
+    pub(super) merge_comments: bool,
+    pub(super) merge_strings: bool,
+    pub(super) lossless: bool,
+    pub(super) max_identifier_length: Option<usize>, // in chars
+    pub(super) ascii_identifiers: bool,
+    pub(super) max_raw_string_pounds: usize,
+    pub(super) source_id: SourceId,
+}
+
+impl Default for LexerOptions {
+    fn default() -> Self {
+        Self {
+            case_insensitive_keywords: false,
+            extra_keywords: vec![],
+            line_comment: "//".to_string(),
+            block_comment: ("/*".to_string(), "*/".to_string()),
+            unknown_tokens: false,
+            merge_comments: false,
+            merge_strings: false,
+            lossless: false,
+            max_identifier_length: None,
+            ascii_identifiers: false,
+            max_raw_string_pounds: 255,
+            source_id: SourceId::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LexerBuilder {
+    options: LexerOptions,
+}
+
+impl LexerBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn case_insensitive_keywords(mut self, enabled: bool) -> Self {
+        self.options.case_insensitive_keywords = enabled;
+        self
+    }
+
+    // lexed as Token::CustomKeyword
+    pub fn keyword(mut self, keyword: &str) -> Self {
+        self.options.extra_keywords.push(keyword.to_string());
+        self
+    }
+
+    pub fn line_comment(mut self, opener: &str) -> Self {
+        self.options.line_comment = opener.to_string();
+        self
+    }
+
+    pub fn block_comment(mut self, opener: &str, terminator: &str) -> Self {
+        self.options.block_comment = (opener.to_string(), terminator.to_string());
+        self
+    }
+
+    // emit Token::Unknown instead of failing with TokenizationError::InvalidToken
+    pub fn unknown_tokens(mut self, enabled: bool) -> Self {
+        self.options.unknown_tokens = enabled;
+        self
+    }
+
+    // emit a single Token::Comment instead of the opener, literal and closer tokens
+    pub fn merge_comments(mut self, enabled: bool) -> Self {
+        self.options.merge_comments = enabled;
+        self
+    }
+
+    // emit a single Token::String for strings without interpolations
+    pub fn merge_strings(mut self, enabled: bool) -> Self {
+        self.options.merge_strings = enabled;
+        self
+    }
+
+    // emit Token::Whitespace and keep the source text of every token, see Lexer::text
+    pub fn lossless(mut self, enabled: bool) -> Self {
+        self.options.lossless = enabled;
+        self
+    }
+
+    // fail with TokenizationError::IdentifierTooLong on longer identifiers
+    pub fn max_identifier_length(mut self, length: usize) -> Self {
+        self.options.max_identifier_length = Some(length);
+        self
+    }
+
+    // only allow ascii letters, digits and _ in identifiers instead of XID_Start and XID_Continue
+    pub fn ascii_identifiers(mut self, enabled: bool) -> Self {
+        self.options.ascii_identifiers = enabled;
+        self
+    }
+
+    // fail with TokenizationError::RawStringPoundLimit on raw strings opened with more #
+    pub fn max_raw_string_pounds(mut self, count: usize) -> Self {
+        self.options.max_raw_string_pounds = count;
+        self
+    }
+
+    // source of the spans returned by Lexer::span
+    pub fn source_id(mut self, source_id: SourceId) -> Self {
+        self.options.source_id = source_id;
+        self
+    }
+
+    pub fn build<'a>(self, source: impl Iterator<Item = char> + Send + 'a) -> Lexer<'a> {
+        Lexer::with_options(source, self.options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use crate::{
+        lexer::{
+            TokenizationError,
+            parsing::EscapeParseError,
+            tokens::{CommentKind, Token},
+        },
+        span::{SourceId, Span},
+    };
+
+    use super::LexerBuilder;
+
+    fn assert_results<const N: usize>(
+        builder: LexerBuilder,
+        source: &str,
+        expected: [Result<Token, TokenizationError>; N],
+    ) {
+        let tokens: Vec<_> = builder.build(source.chars()).collect();
+        let mut expected = Vec::from(expected);
+        if !expected.last().is_some_and(Result::is_err) {
+            expected.push(Ok(Token::Eof)); // not emitted after errors
+        }
+        assert_eq!(tokens, expected)
+    }
+
+    #[rstest]
+    #[case("LET If", [Token::Let, Token::If])]
+    #[case("nULL", [Token::Identifier("nULL".to_string())])] // only keywords
+    #[case("letter", [Token::Identifier("letter".to_string())])]
+    fn case_insensitive_keywords<const N: usize>(
+        #[case] source: &str,
+        #[case] expected: [Token; N],
+    ) {
+        assert_results(
+            LexerBuilder::new().case_insensitive_keywords(true),
+            source,
+            expected.map(Ok),
+        );
+    }
+
+    #[rstest]
+    fn case_sensitive_keywords() {
+        assert_results(
+            LexerBuilder::new(),
+            "LET",
+            [Ok(Token::Identifier("LET".to_string()))],
+        );
+    }
+
+    #[rstest]
+    #[case(
+        "match foo",
+        [Token::CustomKeyword("match".to_string()), Token::Identifier("foo".to_string())]
+    )]
+    #[case("matches", [Token::Identifier("matches".to_string())])]
+    #[case("MATCH", [Token::CustomKeyword("match".to_string())])]
+    fn extra_keywords<const N: usize>(#[case] source: &str, #[case] expected: [Token; N]) {
+        assert_results(
+            LexerBuilder::new()
+                .keyword("match")
+                .case_insensitive_keywords(true),
+            source,
+            expected.map(Ok),
+        );
+    }
+
+    #[rstest]
+    #[case(
+        "# foo\n",
+        [Token::LineCommentOpen, Token::CommentLiteral(" foo".to_string()), Token::CommentClose(CommentKind::Line)]
+    )]
+    #[case(
+        "(* foo *)",
+        [Token::BlockCommentOpen, Token::CommentLiteral(" foo ".to_string()), Token::CommentClose(CommentKind::Block)]
+    )]
+    #[case("// foo", [Token::Slash, Token::Slash, Token::Identifier("foo".to_string())])]
+    fn comment_delimiters<const N: usize>(#[case] source: &str, #[case] expected: [Token; N]) {
+        assert_results(
+            LexerBuilder::new()
+                .line_comment("#")
+                .block_comment("(*", "*)"),
+            source,
+            expected.map(Ok),
+        );
+    }
+
+    #[rstest]
+    #[case("abc", [Ok(Token::Identifier("abc".to_string()))])]
+    #[case("éèà", [Ok(Token::Identifier("éèà".to_string()))])] // counted in chars
+    #[case("abcd", [Err(TokenizationError::IdentifierTooLong("abcd".to_string()))])]
+    #[case("e\u{301}e\u{301}e\u{301}", [Ok(Token::Identifier("ééé".to_string()))])] // after normalization
+    #[case("let", [Ok(Token::Let)])]
+    fn identifier_length<const N: usize>(
+        #[case] source: &str,
+        #[case] expected: [Result<Token, TokenizationError>; N],
+    ) {
+        assert_results(
+            LexerBuilder::new().max_identifier_length(3),
+            source,
+            expected,
+        );
+    }
+
+    #[rstest]
+    #[case("a_1", [Ok(Token::Identifier("a_1".to_string()))])]
+    #[case("é", [Err(TokenizationError::InvalidToken("é".to_string()))])]
+    #[case("aé", [Ok(Token::Identifier("a".to_string())), Err(TokenizationError::InvalidToken("é".to_string()))])]
+    fn ascii_identifiers<const N: usize>(
+        #[case] source: &str,
+        #[case] expected: [Result<Token, TokenizationError>; N],
+    ) {
+        assert_results(
+            LexerBuilder::new().ascii_identifiers(true),
+            source,
+            expected,
+        );
+    }
+
+    #[rstest]
+    #[case("`a`", [Ok(Token::StringOpen), Ok(Token::StringLiteral("a".to_string())), Ok(Token::StringClose)])]
+    #[case("##`a`##", [Ok(Token::StringOpen), Ok(Token::StringLiteral("a".to_string())), Ok(Token::StringClose)])]
+    #[case("###`a`###", [Err(TokenizationError::RawStringPoundLimit(0..3))])]
+    #[case("b #####", [Ok(Token::Identifier("b".to_string())), Err(TokenizationError::RawStringPoundLimit(2..5))])] // stops reading after the limit
+    #[case("##", [Err(TokenizationError::UnterminatedRawStringOpener(0..2))])]
+    fn raw_string_pounds<const N: usize>(
+        #[case] source: &str,
+        #[case] expected: [Result<Token, TokenizationError>; N],
+    ) {
+        assert_results(
+            LexerBuilder::new().max_raw_string_pounds(2),
+            source,
+            expected,
+        );
+    }
+
+    #[rstest]
+    #[case("@", [Token::Unknown('@')])]
+    #[case(
+        "a @€ b",
+        [
+            Token::Identifier("a".to_string()),
+            Token::Unknown('@'),
+            Token::Unknown('€'),
+            Token::Identifier("b".to_string()),
+        ]
+    )]
+    fn unknown_tokens<const N: usize>(#[case] source: &str, #[case] expected: [Token; N]) {
+        assert_results(
+            LexerBuilder::new().unknown_tokens(true),
+            source,
+            expected.map(Ok),
+        );
+    }
+
+    #[rstest]
+    #[case(
+        "// foo\n/* bar */",
+        [
+            Token::Comment { kind: CommentKind::Line, text: " foo".to_string() },
+            Token::Comment { kind: CommentKind::Block, text: " bar ".to_string() },
+        ]
+    )]
+    #[case(
+        "a // foo",
+        [
+            Token::Identifier("a".to_string()),
+            Token::Comment { kind: CommentKind::Line, text: " foo".to_string() },
+        ]
+    )]
+    #[case("//", [Token::Comment { kind: CommentKind::Line, text: "".to_string() }])]
+    #[case(r#""a""#, [Token::StringOpen, Token::StringLiteral("a".to_string()), Token::StringClose])] // strings are not merged
+    fn merged_comments<const N: usize>(#[case] source: &str, #[case] expected: [Token; N]) {
+        assert_results(
+            LexerBuilder::new().merge_comments(true),
+            source,
+            expected.map(Ok),
+        );
+    }
+
+    #[rstest]
+    #[case(r#""a\nb""#, [Ok(Token::String("a\nb".to_string()))])]
+    #[case(r#""""#, [Ok(Token::String("".to_string()))])]
+    #[case("#`a`#", [Ok(Token::String("a".to_string()))])]
+    #[case(
+        r#""a${"b"}c""#,
+        [
+            Ok(Token::StringOpen),
+            Ok(Token::StringLiteral("a".to_string())),
+            Ok(Token::DollarLeftBrace),
+            Ok(Token::String("b".to_string())),
+            Ok(Token::RightBrace),
+            Ok(Token::StringLiteral("c".to_string())),
+            Ok(Token::StringClose),
+        ]
+    )] // interpolated strings keep their raw tokens
+    #[case(r#""a"#, [Ok(Token::StringOpen), Ok(Token::StringLiteral("a".to_string()))])] // unclosed
+    #[case(
+        r#""\a""#,
+        [
+            Ok(Token::StringOpen),
+            Err(TokenizationError::EscapeParse(EscapeParseError::InvalidEscape(r"\a".to_string()), 1..3)),
+        ]
+    )]
+    fn merged_strings<const N: usize>(
+        #[case] source: &str,
+        #[case] expected: [Result<Token, TokenizationError>; N],
+    ) {
+        assert_results(LexerBuilder::new().merge_strings(true), source, expected);
+    }
+
+    #[rstest]
+    #[case(
+        " let a\t= 1\n",
+        [
+            Token::Whitespace(" ".to_string()),
+            Token::Let,
+            Token::Whitespace(" ".to_string()),
+            Token::Identifier("a".to_string()),
+            Token::Whitespace("\t".to_string()),
+            Token::Equal,
+            Token::Whitespace(" ".to_string()),
+            Token::IntLiteral(1),
+            Token::Whitespace("\n".to_string()),
+        ]
+    )]
+    #[case(
+        r#""a ${ b }""#,
+        [
+            Token::StringOpen,
+            Token::StringLiteral("a ".to_string()),
+            Token::DollarLeftBrace,
+            Token::Whitespace(" ".to_string()),
+            Token::Identifier("b".to_string()),
+            Token::Whitespace(" ".to_string()),
+            Token::RightBrace,
+            Token::StringClose,
+        ]
+    )]
+    fn lossless_whitespace<const N: usize>(#[case] source: &str, #[case] expected: [Token; N]) {
+        assert_results(LexerBuilder::new().lossless(true), source, expected.map(Ok));
+    }
+
+    #[rstest]
+    #[case("let  a = 0x1F; // hex\r\n")]
+    #[case(r#""a\n\u{41}${ "b" }" "#)]
+    #[case("<<~END\n    foo\n  END\n")]
+    #[case("#`raw`# /* block\n comment */ 1.5e3")]
+    #[case("// unclosed")]
+    fn lossless_round_trip(#[case] source: &str, #[values(false, true)] merge: bool) {
+        let mut lexer = LexerBuilder::new()
+            .lossless(true)
+            .merge_comments(merge)
+            .merge_strings(merge)
+            .build(source.chars());
+        let mut text = String::new();
+        while let Some(token) = lexer.next() {
+            token.unwrap();
+            text.push_str(lexer.text());
+        }
+        assert_eq!(source, text);
+    }
+
+    #[rstest]
+    fn source_id() {
+        let mut lexer = LexerBuilder::new()
+            .source_id(SourceId(3))
+            .build("a b".chars());
+        lexer.next();
+        lexer.next();
+        assert_eq!(Span::new(SourceId(3), 2..3), lexer.span());
+    }
+}
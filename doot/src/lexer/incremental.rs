@@ -0,0 +1,269 @@
+use std::{ops::Range, slice};
+
+use crate::{
+    edits::{EditError, TextEdit, apply_edits},
+    span::{SourceId, Span},
+};
+
+use super::{TokenizationError, builder::LexerBuilder, tokens::Token};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LexedToken {
+    token: Token,
+    span: Range<usize>,
+    read_end: usize,
+    root: bool,
+    raw: bool,
+    source_id: SourceId,
+}
+
+impl LexedToken {
+    pub fn token(&self) -> &Token {
+        &self.token
+    }
+
+    pub fn span(&self) -> Span {
+        Span::new(self.source_id, self.span.clone())
+    }
+
+    // lexed inside a raw string, where ${ is text
+    pub fn in_raw_string(&self) -> bool {
+        self.raw
+    }
+
+    fn shifted(mut self, delta: isize) -> Self {
+        let shift = |offset: usize| offset.saturating_add_signed(delta);
+        self.span = shift(self.span.start)..shift(self.span.end);
+        self.read_end = shift(self.read_end);
+        self
+    }
+}
+
+// token indexes replaced by an edit, in the token streams before and after it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenChange {
+    removed: Range<usize>,
+    inserted: Range<usize>,
+}
+
+impl TokenChange {
+    pub fn removed(&self) -> &Range<usize> {
+        &self.removed
+    }
+
+    pub fn inserted(&self) -> &Range<usize> {
+        &self.inserted
+    }
+}
+
+// source text kept in sync with its tokens, relexing only the part touched by each edit.
+// sources that do not lex are kept too, with the tokens before the error
+#[derive(Debug, Clone)]
+pub struct LexedSource {
+    source: String,
+    tokens: Vec<LexedToken>,
+    error: Option<(TokenizationError, Span)>,
+    builder: LexerBuilder,
+}
+
+impl LexedSource {
+    pub fn new(source: &str, builder: LexerBuilder) -> Self {
+        let mut error = None;
+        let tokens = Self::lex(&builder, source, 0)
+            .map_while(|token| token.map_err(|err| error = Some(err)).ok())
+            .collect();
+        Self {
+            source: source.to_string(),
+            tokens,
+            error,
+            builder,
+        }
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn tokens(&self) -> &[LexedToken] {
+        &self.tokens
+    }
+
+    // the error lexing stopped at, the span is used if the error does not know its own
+    pub fn error(&self) -> Option<(&TokenizationError, Span)> {
+        self.error.as_ref().map(|(err, span)| (err, *span))
+    }
+
+    // the edit is applied even if the new source does not lex
+    pub fn edit(&mut self, edit: &TextEdit) -> Result<TokenChange, EditError> {
+        let source = apply_edits(&self.source, slice::from_ref(edit))?;
+        let range = edit.range();
+        let delta = edit.new_text().len() as isize - range.len() as isize;
+
+        // tokens that never looked at the edited text are kept, relexing restarts in the root state
+        let mut first = self
+            .tokens
+            .iter()
+            .take_while(|t| t.read_end <= range.start)
+            .count();
+        while first > 0 && !self.tokens.get(first).is_some_and(|t| t.root) {
+            first -= 1;
+        }
+        let restart = first.checked_sub(1).map_or(0, |i| self.tokens[i].span.end);
+
+        // stops once a token starts in the root state where an old token after the edit did.
+        // nothing after an old error was lexed, so relexing goes on up to the new one
+        let mut old = first;
+        let mut relexed = vec![];
+        let mut synced = false;
+        let mut error = None;
+        for token in Self::lex(&self.builder, &source, restart) {
+            let token = match token {
+                Ok(token) => token,
+                Err(err) => {
+                    error = Some(err);
+                    break;
+                }
+            };
+            let shifted_start = |t: &LexedToken| t.span.start.saturating_add_signed(delta);
+            while old < self.tokens.len()
+                && (self.tokens[old].span.start < range.end
+                    || shifted_start(&self.tokens[old]) < token.span.start)
+            {
+                old += 1;
+            }
+            if token.root
+                && self.error.is_none()
+                && self
+                    .tokens
+                    .get(old)
+                    .is_some_and(|t| t.root && shifted_start(t) == token.span.start)
+            {
+                synced = true;
+                break;
+            }
+            relexed.push(token);
+        }
+        if !synced {
+            old = self.tokens.len();
+        }
+
+        let inserted = first..first + relexed.len();
+        let kept: Vec<_> = self.tokens.drain(old..).map(|t| t.shifted(delta)).collect();
+        self.tokens.truncate(first);
+        self.tokens.extend(relexed);
+        self.tokens.extend(kept);
+        self.source = source;
+        self.error = error;
+        Ok(TokenChange {
+            removed: first..old,
+            inserted,
+        })
+    }
+
+    // errors are made absolute too, with the span of the lexer after them
+    fn lex<'a>(
+        builder: &LexerBuilder,
+        source: &'a str,
+        offset: usize,
+    ) -> impl Iterator<Item = Result<LexedToken, (TokenizationError, Span)>> + 'a {
+        let mut lexer = builder.clone().build(source[offset..].chars());
+        std::iter::from_fn(move || {
+            let token = lexer.next()?;
+            let span = offset + lexer.last.span.start..offset + lexer.last.span.end;
+            Some(match token {
+                Ok(token) => Ok(LexedToken {
+                    token,
+                    span,
+                    read_end: offset + lexer.last.read_end,
+                    root: lexer.last.root,
+                    raw: lexer.last.raw,
+                    source_id: lexer.options.source_id,
+                }),
+                Err(err) => {
+                    let err = err.offset(offset);
+                    // prefer the span of the error itself over the token being read
+                    let span = err.span().unwrap_or(span);
+                    Err((err, Span::new(lexer.options.source_id, span)))
+                }
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use crate::{
+        edits::{EditError, TextEdit},
+        lexer::{builder::LexerBuilder, tokens::Token},
+    };
+
+    use super::LexedSource;
+
+    fn relex(source: &str, edit: TextEdit) -> (LexedSource, (usize, usize)) {
+        let mut lexed = LexedSource::new(source, LexerBuilder::new());
+        let change = lexed.edit(&edit).unwrap();
+        let expected = LexedSource::new(lexed.source(), LexerBuilder::new());
+        assert_eq!(expected.tokens(), lexed.tokens());
+        assert_eq!(expected.error(), lexed.error());
+        (lexed, (change.removed().len(), change.inserted().len()))
+    }
+
+    #[rstest]
+    #[case("let a = 1; let b = 2;", TextEdit::new(4..5, "foo"), (1, 1))]
+    #[case("let a = 1; let b = 2;", TextEdit::insert(5, "bc"), (1, 1))]
+    #[case("let a = 1; let b = 2;", TextEdit::delete(3..4), (2, 1))] // let a -> leta
+    #[case("let a = 1; let b = 2;", TextEdit::insert(0, "x "), (0, 1))]
+    #[case("let a = 1; let b = 2;", TextEdit::insert(21, " c"), (1, 2))] // ; looked ahead at the end
+    #[case("a 5.b", TextEdit::insert(4, "6 "), (2, 1))] // 5 looked ahead at b
+    #[case("a \"b c\" d", TextEdit::insert(5, "\""), (5, 7))] // closes the string early
+    #[case("a /* b */ c", TextEdit::delete(7..9), (5, 3))] // unclosed comment, the end is not in the root state
+    #[case("\"${a}\" b", TextEdit::new(3..4, "xy"), (5, 5))] // restarts outside the string
+    #[case("", TextEdit::insert(0, "a"), (0, 1))]
+    #[case("a b", TextEdit::delete(0..3), (2, 0))]
+    #[case("a b c", TextEdit::insert(3, "@"), (3, 1))] // lexing stops at the error
+    #[case("a @ c", TextEdit::delete(2..3), (1, 3))] // fixes the error
+    #[case("a \"\\q\" b", TextEdit::insert(8, "c"), (1, 1))] // relexed up to the same error
+    #[case("let a = 1; b\0", TextEdit::insert(11, "c"), (1, 1))] // the nul span is absolute
+    #[case("=\n", TextEdit::insert(2, "\0"), (1, 0))] // the nul span comes from the error
+    fn edits(#[case] source: &str, #[case] edit: TextEdit, #[case] expected: (usize, usize)) {
+        assert_eq!(expected, relex(source, edit).1);
+    }
+
+    #[rstest]
+    fn kept_tokens_are_shifted() {
+        let (lexed, _) = relex("let a = 1;", TextEdit::new(4..5, "foo"));
+        let spans: Vec<_> = lexed.tokens().iter().map(|t| t.span().range()).collect();
+        assert_eq!(vec![0..3, 4..7, 8..9, 10..11, 11..12, 12..12], spans);
+        assert_eq!(&Token::IntLiteral(1), lexed.tokens()[3].token());
+    }
+
+    #[rstest]
+    fn errors() {
+        let mut lexed = LexedSource::new("a b", LexerBuilder::new());
+        assert_eq!(
+            Err(EditError::OutOfBounds(2..4)),
+            lexed.edit(&TextEdit::delete(2..4))
+        );
+        assert_eq!("a b", lexed.source()); // unchanged after errors
+        assert_eq!(3, lexed.tokens().len());
+        assert!(lexed.error().is_none());
+    }
+
+    #[rstest]
+    fn typing_through_errors() {
+        let text = r#"let s = "a\u{41}" + 0x1f;"#;
+        let mut lexed = LexedSource::new("", LexerBuilder::new());
+        for (offset, ch) in text.char_indices() {
+            lexed
+                .edit(&TextEdit::insert(offset, &ch.to_string()))
+                .unwrap();
+            let expected = LexedSource::new(lexed.source(), LexerBuilder::new());
+            assert_eq!(expected.tokens(), lexed.tokens());
+            assert_eq!(expected.error(), lexed.error());
+        }
+        assert_eq!(text, lexed.source());
+        assert!(lexed.error().is_none());
+    }
+}
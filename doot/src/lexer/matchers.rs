@@ -1,59 +1,61 @@
-use std::{
-    cell::{RefCell, RefMut},
-    rc::Rc,
-};
-
 use super::{TokenizationError, state::LexerStateManager};
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub(super) enum MatcherState {
     Open,
     Broken,
     Closeable,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub(super) enum MatcherClass {
     Fixed,
     Dynamic,
 }
 
 type MatchResult<T> = Result<(T, usize), TokenizationError>;
-
-pub(super) trait Matcher<T> {
-    fn class(&self) -> &MatcherClass;
-    fn state(&self) -> &MatcherState;
-    fn accept(&mut self, buffer: &str, ch: char);
+type StateOp<'a> = Box<dyn FnMut(&str, char) -> MatcherState + Send + 'a>;
+pub(super) type Condition<'a> = Box<dyn FnMut(&str, char) -> bool + Send + 'a>;
+type Closer<'a, T> = Box<dyn FnMut(&str, &mut LexerStateManager) -> MatchResult<T> + Send + 'a>;
+type ChainCloser<'a, T, U, const N: usize> = Box<
+    dyn FnMut(&str, [U; N], &mut LexerStateManager) -> Result<T, TokenizationError> + Send + 'a,
+>;
+
+pub(super) trait Matcher<T>: Send {
+    fn class(&self) -> MatcherClass;
+    fn state(&self) -> MatcherState;
+    fn accept(&mut self, buffer: &str, ch: char) -> Result<(), TokenizationError>;
     fn close(&mut self, buffer: &str, state: &mut LexerStateManager) -> MatchResult<T>;
 }
 
 struct MatcherStateManager<'a> {
     value: MatcherState,
-    op: Box<dyn FnMut(&str, char) -> MatcherState + 'a>,
+    op: StateOp<'a>,
 }
 
 impl<'a> MatcherStateManager<'a> {
-    fn accept(&mut self, buffer: &str, ch: char) {
+    fn accept(&mut self, buffer: &str, ch: char) -> Result<(), TokenizationError> {
         match self.value {
-            MatcherState::Broken => panic!(),
+            MatcherState::Broken => Err(TokenizationError::BrokenMatcher),
             _ => {
                 self.value = if ch == '\0' {
                     MatcherState::Broken
                 } else {
                     self.op.as_mut()(buffer, ch)
-                }
+                };
+                Ok(())
             }
         }
     }
 
-    fn new(state: MatcherState, op: impl FnMut(&str, char) -> MatcherState + 'a) -> Self {
+    fn new(state: MatcherState, op: impl FnMut(&str, char) -> MatcherState + Send + 'a) -> Self {
         Self {
             value: state,
             op: Box::new(op),
         }
     }
 
-    fn chain(states: Vec<Self>, mut before_switch: impl FnMut(&str, &Self) + 'a) -> Self {
+    fn chain(states: Vec<Self>, mut before_switch: impl FnMut(&str, &Self) + Send + 'a) -> Self {
         let mut states = states.into_iter();
         let mut current = None;
         for state in states.by_ref() {
@@ -65,22 +67,24 @@ impl<'a> MatcherStateManager<'a> {
         }
         Self::new(
             match &current {
-                Some(s) => s.value.clone(),
+                Some(s) => s.value,
                 None => MatcherState::Closeable,
             },
             move |buff, ch| match current {
                 Some(ref mut c) => {
-                    c.accept(buff, ch);
+                    if c.accept(buff, ch).is_err() {
+                        return MatcherState::Broken;
+                    }
                     match &c.value {
                         MatcherState::Closeable => match states.next() {
                             Some(s) => {
                                 before_switch(buff, &s);
                                 current = Some(s);
-                                current.as_ref().unwrap().value.clone()
+                                current.as_ref().unwrap().value
                             }
-                            None => c.value.clone(),
+                            None => c.value,
                         },
-                        s => s.clone(),
+                        s => *s,
                     }
                 }
                 None => MatcherState::Broken,
@@ -88,7 +92,7 @@ impl<'a> MatcherStateManager<'a> {
         )
     }
 
-    fn conditions(conditions: Vec<Box<dyn FnMut(&str, char) -> bool + 'a>>) -> Self {
+    fn conditions(conditions: Vec<Condition<'a>>) -> Self {
         Self::chain(
             conditions
                 .into_iter()
@@ -109,13 +113,38 @@ impl<'a> MatcherStateManager<'a> {
     }
 
     fn text(source: &str) -> Self {
-        fn make_filter(c: char) -> Box<dyn FnMut(&str, char) -> bool> {
-            Box::new(move |_, ch| ch == c)
-        }
-        Self::conditions(source.chars().map(make_filter).collect())
+        Self::text_with(source, |expected, ch| ch == expected)
+    }
+
+    fn text_ignore_case(source: &str) -> Self {
+        Self::text_with(source, |expected, ch| {
+            ch.to_lowercase().eq(expected.to_lowercase())
+        })
     }
 
-    fn take_while(mut filter: impl FnMut(&str, char) -> bool + 'a, min: usize) -> Self {
+    // same states as a condition per character, without boxing each of them
+    fn text_with(source: &str, eq: fn(char, char) -> bool) -> Self {
+        let source = source.to_string();
+        let mut matched = 0; // bytes
+        let state = |matched: usize, source: &str| {
+            if matched == source.len() {
+                MatcherState::Closeable
+            } else {
+                MatcherState::Open
+            }
+        };
+        Self::new(state(matched, &source), move |_, ch| {
+            match source[matched..].chars().next() {
+                Some(expected) if eq(expected, ch) => {
+                    matched += expected.len_utf8();
+                    state(matched, &source)
+                }
+                _ => MatcherState::Broken,
+            }
+        })
+    }
+
+    fn take_while(mut filter: impl FnMut(&str, char) -> bool + Send + 'a, min: usize) -> Self {
         let mut count = 0;
         let check_count = move |count| {
             if count < min {
@@ -136,7 +165,7 @@ impl<'a> MatcherStateManager<'a> {
 
     fn filtered_collector<const N: usize>(
         terminators: [String; N],
-        mut filter: impl FnMut(&str, char) -> bool + 'a,
+        mut filter: impl FnMut(&str, char) -> bool + Send + 'a,
     ) -> Self {
         let mut terminated = false;
         Self::new(MatcherState::Open, move |buff, ch| {
@@ -157,20 +186,20 @@ impl<'a> MatcherStateManager<'a> {
 pub(super) struct DefaultMatcher<'a, T> {
     class: MatcherClass,
     state: MatcherStateManager<'a>,
-    closer: Box<dyn FnMut(&str, &mut LexerStateManager) -> MatchResult<T> + 'a>,
+    closer: Closer<'a, T>,
 }
 
 impl<T> Matcher<T> for DefaultMatcher<'_, T> {
-    fn class(&self) -> &MatcherClass {
-        &self.class
+    fn class(&self) -> MatcherClass {
+        self.class
     }
 
-    fn state(&self) -> &MatcherState {
-        &self.state.value
+    fn state(&self) -> MatcherState {
+        self.state.value
     }
 
-    fn accept(&mut self, buffer: &str, ch: char) {
-        self.state.accept(buffer, ch);
+    fn accept(&mut self, buffer: &str, ch: char) -> Result<(), TokenizationError> {
+        self.state.accept(buffer, ch)
     }
 
     fn close(&mut self, buffer: &str, state: &mut LexerStateManager) -> MatchResult<T> {
@@ -178,11 +207,12 @@ impl<T> Matcher<T> for DefaultMatcher<'_, T> {
     }
 }
 
-impl<'a, T: 'a + Clone> DefaultMatcher<'a, T> {
+impl<'a, T: 'a + Clone + Send> DefaultMatcher<'a, T> {
+    #[allow(clippy::new_ret_no_self)]
     fn new(
         class: MatcherClass,
         state: MatcherStateManager<'a>,
-        closer: impl FnMut(&str, &mut LexerStateManager) -> MatchResult<T> + 'a,
+        closer: impl FnMut(&str, &mut LexerStateManager) -> MatchResult<T> + Send + 'a,
     ) -> Box<dyn Matcher<T> + 'a> {
         Box::new(Self {
             class,
@@ -201,8 +231,8 @@ impl<'a, T: 'a + Clone> DefaultMatcher<'a, T> {
     }
 
     pub(super) fn conditions(
-        conditions: Vec<Box<dyn FnMut(&str, char) -> bool + 'a>>,
-        closer: impl FnMut(&str, &mut LexerStateManager) -> Result<T, TokenizationError> + 'a,
+        conditions: Vec<Condition<'a>>,
+        closer: impl FnMut(&str, &mut LexerStateManager) -> Result<T, TokenizationError> + Send + 'a,
     ) -> Box<dyn Matcher<T> + 'a> {
         Self::new(
             MatcherClass::Dynamic,
@@ -213,7 +243,7 @@ impl<'a, T: 'a + Clone> DefaultMatcher<'a, T> {
 
     pub(super) fn text(
         source: &str,
-        closer: impl FnMut(&str, &mut LexerStateManager) -> Result<T, TokenizationError> + 'a,
+        closer: impl FnMut(&str, &mut LexerStateManager) -> Result<T, TokenizationError> + Send + 'a,
     ) -> Box<dyn Matcher<T> + 'a> {
         Self::new(
             MatcherClass::Fixed,
@@ -226,11 +256,21 @@ impl<'a, T: 'a + Clone> DefaultMatcher<'a, T> {
         Self::text(source, move |_, _| Ok(result.clone()))
     }
 
+    pub(super) fn simple_text_ignore_case(source: &str, result: T) -> Box<dyn Matcher<T> + 'a> {
+        Self::new(
+            MatcherClass::Fixed,
+            MatcherStateManager::text_ignore_case(source),
+            Self::full_match_closer(move |_, _| Ok(result.clone())),
+        )
+    }
+
     pub(super) fn filtered_collector<const N: usize>(
         terminators: [&str; N],
-        filter: impl FnMut(&str, char) -> bool + 'a,
+        filter: impl FnMut(&str, char) -> bool + Send + 'a,
         consume_terminator: bool,
-        mut closer: impl FnMut(&str, &str, &mut LexerStateManager) -> Result<T, TokenizationError> + 'a,
+        mut closer: impl FnMut(&str, &str, &mut LexerStateManager) -> Result<T, TokenizationError>
+        + Send
+        + 'a,
     ) -> Box<dyn Matcher<T> + 'a> {
         let terminators = terminators.map(String::from);
         Self::new(
@@ -256,15 +296,29 @@ impl<'a, T: 'a + Clone> DefaultMatcher<'a, T> {
     pub(super) fn collector<const N: usize>(
         terminators: [&str; N],
         consume_terminator: bool,
-        closer: impl FnMut(&str, &str, &mut LexerStateManager) -> Result<T, TokenizationError> + 'a,
+        closer: impl FnMut(&str, &str, &mut LexerStateManager) -> Result<T, TokenizationError>
+        + Send
+        + 'a,
     ) -> Box<dyn Matcher<T> + 'a> {
         Self::filtered_collector(terminators, |_, _| true, consume_terminator, closer)
     }
 
+    pub(super) fn custom(
+        state: MatcherState,
+        op: impl FnMut(&str, char) -> MatcherState + Send + 'a,
+        closer: impl FnMut(&str, &mut LexerStateManager) -> MatchResult<T> + Send + 'a, // may consume only part of the buffer
+    ) -> Box<dyn Matcher<T> + 'a> {
+        Self::new(
+            MatcherClass::Dynamic,
+            MatcherStateManager::new(state, op),
+            closer,
+        )
+    }
+
     pub(super) fn take_while(
-        filter: impl FnMut(&str, char) -> bool + 'a,
+        filter: impl FnMut(&str, char) -> bool + Send + 'a,
         min: usize,
-        closer: impl FnMut(&str, &mut LexerStateManager) -> Result<T, TokenizationError> + 'a,
+        closer: impl FnMut(&str, &mut LexerStateManager) -> Result<T, TokenizationError> + Send + 'a,
     ) -> Box<dyn Matcher<T> + 'a> {
         Self::new(
             MatcherClass::Dynamic,
@@ -281,93 +335,91 @@ impl<'a> DefaultMatcher<'a, String> {
 }
 
 pub(super) struct ChainMatcher<'a, T, U: Clone, const N: usize> {
-    class: MatcherClass,
-    matchers: Rc<RefCell<[Box<dyn Matcher<U> + 'a>; N]>>,
-    buffer_indexes: Rc<RefCell<Vec<usize>>>,
-    state: MatcherStateManager<'a>,
-    closer:
-        Box<dyn FnMut(&str, [U; N], &mut LexerStateManager) -> Result<T, TokenizationError> + 'a>,
+    matchers: [Box<dyn Matcher<U> + 'a>; N],
+    current: usize,
+    buffer_indexes: Vec<usize>, // where each started matcher begins in the buffer
+    value: MatcherState,
+    closer: ChainCloser<'a, T, U, N>,
 }
 
 impl<'a, T: 'a, U: 'a + Clone, const N: usize> ChainMatcher<'a, T, U, N> {
+    #[allow(clippy::new_ret_no_self)]
     pub(super) fn new(
         matchers: [Box<dyn Matcher<U>>; N],
         closer: impl FnMut(&str, [U; N], &mut LexerStateManager) -> Result<T, TokenizationError>
-        + 'a + 'a,
+        + Send
+        + 'a,
     ) -> Box<dyn Matcher<T> + 'a> {
-        let len = matchers.len();
-        let matchers = Rc::new(RefCell::new(matchers));
-        let buffer_indexes = Rc::new(RefCell::new(vec![]));
+        // matchers that can already close match nothing and are skipped
+        let mut buffer_indexes = vec![];
+        let mut current = 0;
+        for matcher in &matchers {
+            buffer_indexes.push(0);
+            if matcher.state() != MatcherState::Closeable {
+                break;
+            }
+            current += 1;
+        }
         Box::new(Self {
-            class: MatcherClass::Dynamic,
-            matchers: matchers.clone(),
-            buffer_indexes: buffer_indexes.clone(),
-            state: MatcherStateManager::chain(
-                (0..len)
-                    .map(|i| {
-                        let state = matchers.borrow()[i].state().clone();
-                        let matchers = matchers.clone();
-                        MatcherStateManager::new(state, move |buffer, ch| -> MatcherState {
-                            let mut m = matchers.borrow_mut();
-                            m[i].accept(buffer, ch);
-                            m[i].state().clone()
-                        })
-                    })
-                    .collect(),
-                move |buffer, _| {
-                    let buffer_indexes: Rc<RefCell<Vec<usize>>> = buffer_indexes.clone();
-                    let mut b = buffer_indexes.borrow_mut();
-                    b.push(buffer.len());
-                },
-            ),
+            value: matchers
+                .get(current)
+                .map_or(MatcherState::Closeable, |m| m.state()),
+            matchers,
+            current,
+            buffer_indexes,
             closer: Box::new(closer),
         })
     }
-
-    fn buffer_start_static(buffer_ranges: &RefMut<'_, Vec<usize>>) -> usize {
-        buffer_ranges.last().unwrap().clone()
-    }
-
-    fn buffer_start(&self) -> usize {
-        Self::buffer_start_static(&self.buffer_indexes.borrow_mut())
-    }
 }
 
 impl<'a, T: 'a, U: Clone + 'a, const N: usize> Matcher<T> for ChainMatcher<'a, T, U, N> {
-    fn class(&self) -> &MatcherClass {
-        &self.class
+    fn class(&self) -> MatcherClass {
+        MatcherClass::Dynamic
     }
 
-    fn state(&self) -> &MatcherState {
-        &self.state.value
+    fn state(&self) -> MatcherState {
+        self.value
     }
 
-    fn accept(&mut self, buffer: &str, ch: char) {
-        let start = self.buffer_start();
-        self.state.accept(&buffer[start..], ch);
+    fn accept(&mut self, buffer: &str, ch: char) -> Result<(), TokenizationError> {
+        if self.value == MatcherState::Broken {
+            return Err(TokenizationError::BrokenMatcher);
+        }
+        let Some(matcher) = self.matchers.get_mut(self.current).filter(|_| ch != '\0') else {
+            self.value = MatcherState::Broken;
+            return Ok(());
+        };
+        let start = *self.buffer_indexes.last().unwrap();
+        self.value = match matcher.accept(&buffer[start..], ch) {
+            Err(_) => MatcherState::Broken,
+            Ok(_) => match matcher.state() {
+                // the next matcher starts after the characters of this one
+                MatcherState::Closeable if self.current + 1 < N => {
+                    self.current += 1;
+                    self.buffer_indexes.push(buffer.len());
+                    self.matchers[self.current].state()
+                }
+                state => state,
+            },
+        };
+        Ok(())
     }
 
     fn close(&mut self, buffer: &str, state: &mut LexerStateManager) -> MatchResult<T> {
-        let mut matchers = self.matchers.borrow_mut();
         let buffer_indexes: Vec<usize> = self
             .buffer_indexes
-            .borrow_mut()
             .iter()
-            .chain([buffer.len()].iter())
-            .map(usize::clone)
+            .copied()
+            .chain([buffer.len()])
             .collect();
         let results = std::array::from_fn(|i| {
-            matchers[i].close(&buffer[buffer_indexes[i]..buffer_indexes[i + 1]], state)
+            self.matchers[i].close(&buffer[buffer_indexes[i]..buffer_indexes[i + 1]], state)
         });
         if let Some(err) = results.iter().find_map(|r| r.as_ref().err()) {
             Err(err.clone())
         } else {
-            self.closer.as_mut()(
-                &buffer,
-                results.map(|r| r.clone().unwrap()).map(|r| r.0),
-                state,
-            )
-            .map(|t| (t, buffer.len()))
+            self.closer.as_mut()(buffer, results.map(|r| r.unwrap().0), state)
+                .map(|t| (t, buffer.len()))
         }
     }
 }
@@ -375,10 +427,9 @@ impl<'a, T: 'a, U: Clone + 'a, const N: usize> Matcher<T> for ChainMatcher<'a, T
 #[cfg(test)]
 mod tests {
     use rstest::{fixture, rstest};
-    use utils::testing::assert_panics;
 
     use crate::lexer::{
-        LexerStateManager, MatcherState,
+        LexerStateManager, MatcherState, TokenizationError,
         matchers::{ChainMatcher, DefaultMatcher},
     };
 
@@ -388,7 +439,7 @@ mod tests {
         buffer: String,
     }
 
-    impl<'a> Context {
+    impl Context {
         fn setup() -> Self {
             Self {
                 buffer: String::new(),
@@ -397,12 +448,12 @@ mod tests {
 
         fn state_accept(&mut self, ch: char, state: &mut MatcherStateManager) {
             self.buffer.push(ch);
-            state.accept(&self.buffer, ch);
+            state.accept(&self.buffer, ch).unwrap();
         }
 
         fn matcher_accept<T>(&mut self, ch: char, matcher: &mut Box<dyn Matcher<T>>) {
             self.buffer.push(ch);
-            matcher.accept(&self.buffer, ch);
+            matcher.accept(&self.buffer, ch).unwrap();
         }
 
         fn close<T>(&self, mut matcher: Box<dyn Matcher<T>>) -> usize {
@@ -430,13 +481,13 @@ mod tests {
             }
         });
         assert_eq!(MatcherState::Open, state.value);
-        state.accept("", 'a'); // 0 -> 1
+        state.accept("", 'a').unwrap(); // 0 -> 1
         assert_eq!(MatcherState::Open, state.value);
-        state.accept("", 'a'); // 1 -> 2
+        state.accept("", 'a').unwrap(); // 1 -> 2
         assert_eq!(MatcherState::Open, state.value);
-        state.accept("", 'a'); // 2 -> 3
+        state.accept("", 'a').unwrap(); // 2 -> 3
         assert_eq!(MatcherState::Broken, state.value);
-        assert_panics(|| state.accept("", 'a')); // panic accepting when broken
+        assert_eq!(Err(TokenizationError::BrokenMatcher), state.accept("", 'a'));
     }
 
     #[rstest]
@@ -444,7 +495,7 @@ mod tests {
         let mut state =
             MatcherStateManager::new(MatcherState::Open, |_, _| MatcherState::Closeable);
         assert_eq!(MatcherState::Open, state.value);
-        state.accept("", '\0');
+        state.accept("", '\0').unwrap();
         assert_eq!(MatcherState::Broken, state.value);
     }
 
@@ -505,6 +556,18 @@ mod tests {
         assert_eq!(MatcherState::Broken, state.value);
     }
 
+    #[rstest]
+    fn text_ignore_case(mut ctx: Context) {
+        let mut state = MatcherStateManager::text_ignore_case("hEllo");
+        for ch in "HelLo".chars() {
+            assert_eq!(MatcherState::Open, state.value);
+            ctx.state_accept(ch, &mut state);
+        }
+        assert_eq!(MatcherState::Closeable, state.value);
+        ctx.state_accept('o', &mut state); // any char, it will break because of no text left
+        assert_eq!(MatcherState::Broken, state.value);
+    }
+
     #[rstest]
     fn text_fail(mut ctx: Context) {
         let mut state = MatcherStateManager::text("hello");
@@ -718,19 +781,19 @@ mod tests {
                 Ok(())
             },
         );
-        assert_eq!(MatcherState::Open, *matcher.state());
+        assert_eq!(MatcherState::Open, matcher.state());
         ctx.matcher_accept('a', &mut matcher);
-        assert_eq!(MatcherState::Open, *matcher.state());
+        assert_eq!(MatcherState::Open, matcher.state());
         ctx.matcher_accept('b', &mut matcher);
-        assert_eq!(MatcherState::Open, *matcher.state());
+        assert_eq!(MatcherState::Open, matcher.state());
         ctx.matcher_accept('c', &mut matcher);
-        assert_eq!(MatcherState::Open, *matcher.state());
+        assert_eq!(MatcherState::Open, matcher.state());
         ctx.matcher_accept('d', &mut matcher);
-        assert_eq!(MatcherState::Closeable, *matcher.state());
+        assert_eq!(MatcherState::Closeable, matcher.state());
         ctx.matcher_accept('e', &mut matcher);
-        assert_eq!(MatcherState::Closeable, *matcher.state());
+        assert_eq!(MatcherState::Closeable, matcher.state());
         ctx.matcher_accept('f', &mut matcher);
-        assert_eq!(MatcherState::Closeable, *matcher.state());
+        assert_eq!(MatcherState::Closeable, matcher.state());
         ctx.close(matcher);
     }
 }
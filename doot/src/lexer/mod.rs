@@ -1,10 +1,15 @@
-use std::{char, error::Error, fmt::Display};
+use std::{char, collections::VecDeque, error::Error, fmt::Display, ops::Range, panic, thread};
 
-use matchers::{Matcher, MatcherState};
+use crate::{Source, edits::TextEdit, span::Span};
+
+use builder::{LexerBuilder, LexerOptions};
+use matchers::MatcherState;
 use parsing::{EscapeParseError, NumberParseError, UnicodeParseError};
 use state::LexerStateManager;
-use tokens::Token;
+use tokens::{CommentKind, Token};
 
+pub mod builder;
+pub mod incremental;
 mod matchers;
 mod parsing;
 mod state;
@@ -13,10 +18,94 @@ pub mod tokens;
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum TokenizationError {
     InvalidToken(String),
-    NoEscape,
-    EscapeParse(EscapeParseError),
-    UnicodeParse(UnicodeParseError),
+    IdentifierTooLong(String),
+    NoEscape(Range<usize>), // span of the escape, as for the other escape errors
+    EscapeParse(EscapeParseError, Range<usize>),
+    UnicodeParse(UnicodeParseError, Range<usize>),
     NumberParse(NumberParseError),
+    NulCharacter(Range<usize>),
+    StateUnderflow,
+    BrokenMatcher,
+    UnterminatedRawStringOpener(Range<usize>), // # not followed by `
+    RawStringPoundLimit(Range<usize>),
+    UnterminatedHeredocOpener(Range<usize>), // <<~ not followed by a delimiter and a newline
+}
+
+impl TokenizationError {
+    // matchers only see the buffer, spans in their errors start at 0
+    fn offset(self, offset: usize) -> Self {
+        let shift = |span: Range<usize>| span.start + offset..span.end + offset;
+        match self {
+            TokenizationError::UnterminatedRawStringOpener(span) => {
+                TokenizationError::UnterminatedRawStringOpener(shift(span))
+            }
+            TokenizationError::RawStringPoundLimit(span) => {
+                TokenizationError::RawStringPoundLimit(shift(span))
+            }
+            TokenizationError::UnterminatedHeredocOpener(span) => {
+                TokenizationError::UnterminatedHeredocOpener(shift(span))
+            }
+            TokenizationError::NoEscape(span) => TokenizationError::NoEscape(shift(span)),
+            TokenizationError::NulCharacter(span) => TokenizationError::NulCharacter(shift(span)),
+            TokenizationError::EscapeParse(err, span) => {
+                TokenizationError::EscapeParse(err, shift(span))
+            }
+            TokenizationError::UnicodeParse(err, span) => {
+                TokenizationError::UnicodeParse(err, shift(span))
+            }
+            err => err,
+        }
+    }
+
+    // stable code, explained in diagnostics::CODES
+    pub fn code(&self) -> &'static str {
+        match self {
+            TokenizationError::InvalidToken(_) => "E0001",
+            TokenizationError::IdentifierTooLong(_) => "E0002",
+            TokenizationError::NoEscape(_) => "E0003",
+            TokenizationError::EscapeParse(EscapeParseError::InvalidEscape(_), _) => "E0004",
+            TokenizationError::EscapeParse(EscapeParseError::NoValue(_), _) => "E0005",
+            TokenizationError::EscapeParse(EscapeParseError::InvalidHex(_), _) => "E0006",
+            TokenizationError::EscapeParse(EscapeParseError::HexOutOfRange(_), _) => "E0007",
+            TokenizationError::UnicodeParse(UnicodeParseError::InvalidHex(_), _) => "E0008",
+            TokenizationError::UnicodeParse(UnicodeParseError::InvalidValue(_), _) => "E0009",
+            TokenizationError::NumberParse(_) => "E0010",
+            TokenizationError::NulCharacter(_) => "E0011",
+            TokenizationError::UnterminatedRawStringOpener(_) => "E0012",
+            TokenizationError::RawStringPoundLimit(_) => "E0013",
+            TokenizationError::StateUnderflow | TokenizationError::BrokenMatcher => "E0014",
+            TokenizationError::UnterminatedHeredocOpener(_) => "E0015",
+        }
+    }
+
+    // for the errors with an obvious one
+    pub fn fix(&self) -> Option<TextEdit> {
+        match self {
+            // keep the backslash as text
+            TokenizationError::NoEscape(span)
+            | TokenizationError::EscapeParse(EscapeParseError::InvalidEscape(_), span) => {
+                Some(TextEdit::insert(span.start, "\\"))
+            }
+            TokenizationError::EscapeParse(EscapeParseError::HexOutOfRange(hex), span) => {
+                Some(TextEdit::new(span.clone(), &format!("\\u{{{}}}", hex)))
+            }
+            _ => None,
+        }
+    }
+
+    // of the source text causing the error, for the errors that know it
+    pub fn span(&self) -> Option<Range<usize>> {
+        match self {
+            TokenizationError::UnterminatedRawStringOpener(span)
+            | TokenizationError::RawStringPoundLimit(span)
+            | TokenizationError::UnterminatedHeredocOpener(span)
+            | TokenizationError::NoEscape(span)
+            | TokenizationError::NulCharacter(span)
+            | TokenizationError::EscapeParse(_, span)
+            | TokenizationError::UnicodeParse(_, span) => Some(span.clone()),
+            _ => None,
+        }
+    }
 }
 
 impl Error for TokenizationError {}
@@ -24,144 +113,403 @@ impl Display for TokenizationError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             TokenizationError::InvalidToken(token) => format!("invalid token {}", token),
-            TokenizationError::NoEscape => {
-                format!("missing escaped character")
+            TokenizationError::IdentifierTooLong(name) => {
+                format!("identifier {} is too long", name)
             }
-            TokenizationError::EscapeParse(EscapeParseError::InvalidEscape(escape)) => {
+            TokenizationError::NoEscape(_) => "missing escaped character".to_string(),
+            TokenizationError::EscapeParse(EscapeParseError::InvalidEscape(escape), _) => {
                 format!("invalid escape {}", escape)
             }
-            TokenizationError::EscapeParse(EscapeParseError::NoValue(escape)) => {
+            TokenizationError::EscapeParse(EscapeParseError::NoValue(escape), _) => {
                 format!("missing value for escape {}", escape)
             }
-            TokenizationError::UnicodeParse(UnicodeParseError::InvalidHex(hex)) => {
+            TokenizationError::EscapeParse(EscapeParseError::InvalidHex(hex), _) => {
+                format!("invalid hex escape \\x{}", hex)
+            }
+            TokenizationError::EscapeParse(EscapeParseError::HexOutOfRange(hex), _) => {
+                format!("hex escape \\x{} is not ascii", hex)
+            }
+            TokenizationError::UnicodeParse(UnicodeParseError::InvalidHex(hex), _) => {
                 format!("invalid hex value {}", hex)
             }
-            TokenizationError::UnicodeParse(UnicodeParseError::InvalidValue(unicode)) => {
+            TokenizationError::UnicodeParse(UnicodeParseError::InvalidValue(unicode), _) => {
                 format!("invalid unicode value {}", unicode)
             }
             TokenizationError::NumberParse(err) => format!("number parse error: {}", err),
+            TokenizationError::NulCharacter(_) => "unexpected NUL character".to_string(),
+            TokenizationError::StateUnderflow => "closed more lexer states than opened".to_string(),
+            TokenizationError::BrokenMatcher => {
+                "matcher accepted a character after breaking".to_string()
+            }
+            TokenizationError::UnterminatedRawStringOpener(_) => {
+                "raw string opener is not followed by `".to_string()
+            }
+            TokenizationError::RawStringPoundLimit(_) => {
+                "too many # in raw string opener".to_string()
+            }
+            TokenizationError::UnterminatedHeredocOpener(_) => {
+                "heredoc opener is not followed by a newline".to_string()
+            }
         }
         .fmt(f)
     }
 }
 
+// stops at the first NUL character, so '\0' can be used as the end of input sentinel
+struct SourceReader<'a> {
+    chars: Box<dyn Iterator<Item = char> + Send + 'a>,
+    lookahead: VecDeque<char>, // read again before the source
+    offset: usize,             // bytes read
+    nul: Option<usize>,        // offset of the NUL character
+}
+
+impl<'a> SourceReader<'a> {
+    fn new(chars: impl Iterator<Item = char> + Send + 'a) -> Self {
+        Self {
+            chars: Box::new(chars),
+            lookahead: VecDeque::new(),
+            offset: 0,
+            nul: None,
+        }
+    }
+
+    fn unread(&mut self, chars: impl DoubleEndedIterator<Item = char>) {
+        for ch in chars.rev() {
+            self.lookahead.push_front(ch);
+        }
+    }
+}
+
+impl Iterator for SourceReader<'_> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(ch) = self.lookahead.pop_front() {
+            return Some(ch);
+        }
+        if self.nul.is_some() {
+            return None;
+        }
+        let ch = self.chars.next()?;
+        if ch == '\0' {
+            self.nul = Some(self.offset);
+            return None;
+        }
+        self.offset += ch.len_utf8();
+        Some(ch)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct TokenInfo {
+    span: Range<usize>,
+    read_end: usize, // end of the characters looked at to produce the token
+    root: bool,      // lexed in the root state
+    raw: bool,       // lexed inside a raw string
+    text: String,    // only kept in lossless mode
+}
+
 pub struct Lexer<'a> {
-    source: Box<dyn Iterator<Item = char> + 'a>,
+    source: SourceReader<'a>,
     buffer: String,
+    candidates: Vec<usize>, // reused to avoid allocations
+    offset: usize,          // of the buffer in the source
     state: LexerStateManager,
-    failed: bool,
+    options: LexerOptions,
+    pending: VecDeque<(Result<Token, TokenizationError>, TokenInfo)>,
+    last: TokenInfo,
+    finished: bool,
 }
 
 impl<'a> Lexer<'a> {
-    pub fn new(source: impl Iterator<Item = char> + 'a) -> Self {
+    pub fn new(source: impl Iterator<Item = char> + Send + 'a) -> Self {
+        LexerBuilder::new().build(source)
+    }
+
+    fn with_options(source: impl Iterator<Item = char> + Send + 'a, options: LexerOptions) -> Self {
         Self {
-            source: Box::new(source),
+            source: SourceReader::new(source),
             buffer: String::new(),
+            candidates: vec![],
+            offset: 0,
             state: LexerStateManager::new(),
-            failed: false,
+            options,
+            pending: VecDeque::new(),
+            last: TokenInfo::default(),
+            finished: false,
         }
     }
 
-    fn clean_buffer(&self) -> String {
-        self.buffer.trim_end_matches('\0').to_string()
+    // of the last token returned
+    pub fn span(&self) -> Span {
+        Span::new(self.options.source_id, self.last.span.clone())
     }
-}
 
-type MatcherBox = Box<dyn Matcher<Token>>;
-struct Candidate<'a> {
-    matcher: &'a mut MatcherBox,
-    previous_state: Option<MatcherState>,
-}
+    // source text of the last token returned, empty unless lexing in lossless mode
+    pub fn text(&self) -> &str {
+        &self.last.text
+    }
 
-impl<'a> Iterator for Lexer<'a> {
-    type Item = Result<Token, TokenizationError>;
+    fn end(&mut self) -> Option<Result<Token, TokenizationError>> {
+        self.finished = true;
+        if let Some(offset) = self.source.nul.take() {
+            return Some(Err(TokenizationError::NulCharacter(offset..offset + 1)));
+        }
+        let end = self.source.offset;
+        self.last = TokenInfo {
+            span: end..end,
+            read_end: end + 1, // the end of the source is looked at
+            root: self.state.is_root(),
+            raw: self.state.in_raw_string(),
+            text: String::new(),
+        };
+        Some(Ok(Token::Eof))
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.failed {
-            return None;
+    // collects the literals up to the closing token, the raw tokens are emitted if they can't be merged
+    fn merge(&mut self, opener: Token) -> Option<Result<Token, TokenizationError>> {
+        let kind = match opener {
+            Token::LineCommentOpen => Some(CommentKind::Line),
+            Token::BlockCommentOpen => Some(CommentKind::Block),
+            _ => None,
+        };
+        let mut raw = vec![(Ok(opener), self.last.clone())];
+        let mut eof = None;
+        let closed = loop {
+            match self.next_token() {
+                Some(Ok(token @ (Token::CommentLiteral(_) | Token::StringLiteral(_)))) => {
+                    raw.push((Ok(token), self.last.clone()))
+                }
+                Some(Ok(Token::CommentClose(_) | Token::StringClose)) => break true,
+                Some(Ok(Token::Eof)) if kind.is_some() => {
+                    eof = Some(self.last.clone()); // comments can end with the source
+                    break true;
+                }
+                Some(other) => {
+                    raw.push((other, self.last.clone()));
+                    break false;
+                }
+                None => break false,
+            }
+        };
+        if !closed {
+            self.pending.extend(raw);
+            return self.next_pending();
+        }
+        let opener = &raw[0].1;
+        let last = &raw[raw.len() - 1].1;
+        let closer = if eof.is_some() { last } else { &self.last };
+        let info = TokenInfo {
+            span: opener.span.start..closer.span.end,
+            read_end: self.last.read_end,
+            root: opener.root,
+            raw: opener.raw,
+            text: raw
+                .iter()
+                .map(|(_, info)| info.text.as_str())
+                .chain([if eof.is_some() {
+                    ""
+                } else {
+                    self.last.text.as_str()
+                }])
+                .collect(),
+        };
+        if let Some(eof) = eof {
+            self.pending.push_back((Ok(Token::Eof), eof));
         }
-        let mut matchers = self.state.get().matchers();
-        let mut candidates: Vec<_> = matchers
-            .iter_mut()
-            .map(|m| Candidate {
-                matcher: m,
-                previous_state: None,
+        self.last = info;
+        let text = raw
+            .iter()
+            .map(|(token, _)| token)
+            .filter_map(|token| match token {
+                Ok(Token::CommentLiteral(literal) | Token::StringLiteral(literal)) => {
+                    Some(literal.as_str())
+                }
+                _ => None,
             })
             .collect();
-        let mut matching = false;
+        Some(Ok(match kind {
+            Some(kind) => Token::Comment { kind, text },
+            None => Token::String(text),
+        }))
+    }
+
+    fn next_pending(&mut self) -> Option<Result<Token, TokenizationError>> {
+        self.pending.pop_front().map(|(token, info)| {
+            self.last = info;
+            token
+        })
+    }
 
-        let starting_buffer = self.clean_buffer();
-        let mut source = starting_buffer
-            .chars()
-            .chain(self.source.by_ref())
-            .peekable();
-        if source.peek().is_none() {
+    fn next_token(&mut self) -> Option<Result<Token, TokenizationError>> {
+        if self.finished {
             return None;
         }
-        self.buffer.clear();
-        for ch in source.chain(['\0']) {
+        let root = self.state.is_root();
+        let raw = self.state.in_raw_string();
+        let mut start = self.offset;
+        let mut matchers = self.state.get().matchers(&self.options);
+        let mut candidates = std::mem::take(&mut self.candidates);
+        candidates.clear();
+        candidates.extend(0..matchers.len());
+        let mut longest_match = None;
+        let mut matching = false;
+
+        let mut source = self.source.by_ref().chain(['\0']);
+        let result = loop {
+            let Some(ch) = source.next() else {
+                break None;
+            };
             if !matching
-                && (ch == '\0' || (self.state.get().ignore_whitespace() && ch.is_whitespace()))
+                && (ch == '\0'
+                    || (self.state.get().ignore_whitespace()
+                        && !self.options.lossless
+                        && ch.is_whitespace()))
             {
+                start += ch.len_utf8();
                 continue;
             }
             matching = true;
             self.buffer.push(ch);
-            candidates.iter_mut().for_each(|c| {
-                c.previous_state = Some(c.matcher.state().clone());
-                c.matcher.accept(&self.buffer, ch);
-            });
-            if candidates
+            if let Err(err) = candidates
+                .iter()
+                .try_for_each(|i| matchers[*i].accept(&self.buffer, ch))
+            {
+                self.finished = true;
+                return Some(Err(err));
+            }
+            candidates.retain(|i| matchers[*i].state() != MatcherState::Broken);
+            if let Some(i) = candidates
                 .iter()
-                .any(|c| *c.matcher.state() != MatcherState::Broken)
+                .filter(|i| matchers[**i].state() == MatcherState::Closeable)
+                .min_by_key(|i| matchers[**i].class())
+            {
+                longest_match = Some((*i, self.buffer.len()));
+            }
+            if candidates.is_empty() {
+                let read_end = start + self.buffer.len();
+                let mut text = String::new();
+                let result = longest_match
+                    .map(|(i, length)| matchers[i].close(&self.buffer[..length], &mut self.state))
+                    .unwrap_or_else(|| {
+                        if self.options.unknown_tokens {
+                            let unknown = self.buffer.chars().next().unwrap();
+                            Ok((Token::Unknown(unknown), unknown.len_utf8()))
+                        } else {
+                            Err(TokenizationError::InvalidToken(
+                                self.buffer.trim_end_matches('\0').to_string(),
+                            ))
+                        }
+                    })
+                    .map(|(tok, n_drained)| {
+                        let drained = self.buffer.drain(..n_drained);
+                        if self.options.lossless {
+                            text = drained.collect();
+                        }
+                        self.offset = start + n_drained;
+                        tok
+                    })
+                    .map_err(|err| err.offset(start))
+                    .inspect_err(|_| self.finished = true);
+                self.last = TokenInfo {
+                    span: start..self.offset.max(start), // empty on errors
+                    read_end,
+                    root,
+                    raw,
+                    text,
+                };
+                break Some(result);
+            }
+        };
+        let Some(result) = result else {
+            return self.end();
+        };
+        // the characters read past the token are read again by the next one
+        self.source
+            .unread(self.buffer.drain(..).filter(|ch| *ch != '\0'));
+        self.candidates = candidates;
+        Some(result)
+    }
+}
+
+impl<'a> Iterator for Lexer<'a> {
+    type Item = Result<Token, TokenizationError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.pending.is_empty() {
+            return self.next_pending();
+        }
+        match self.next_token()? {
+            Ok(token @ (Token::LineCommentOpen | Token::BlockCommentOpen))
+                if self.options.merge_comments =>
             {
-                candidates = candidates
-                    .into_iter()
-                    .filter(|c| *c.matcher.state() != MatcherState::Broken)
-                    .collect();
-            } else {
-                let mut matchers: Vec<_> = candidates
-                    .into_iter()
-                    .filter(|c| c.previous_state == Some(MatcherState::Closeable))
-                    .map(|c| c.matcher)
-                    .collect();
-                matchers.sort_by(|m1, m2| m1.class().cmp(m2.class()));
-                return Some(
-                    matchers
-                        .iter_mut()
-                        .next()
-                        .map(|m| {
-                            m.close(&self.buffer[..&self.buffer.len() - 1], &mut self.state)
-                                .map(|(tok, n_drained)| {
-                                    self.buffer.drain(..n_drained);
-                                    tok
-                                })
-                        })
-                        .unwrap_or_else(|| {
-                            Err(TokenizationError::InvalidToken(self.clean_buffer()))
-                        })
-                        .inspect_err(|_| self.failed = true),
-                );
+                self.merge(token)
             }
+            Ok(token @ Token::StringOpen) if self.options.merge_strings => self.merge(token),
+            result => Some(result),
         }
-        None
     }
 }
 
+// lexes the sources in parallel, one lexer per source and the results in the same order
+pub fn lex_many<S: Source + Sync>(
+    sources: &[S],
+    builder: &LexerBuilder,
+) -> Vec<Result<Vec<Token>, TokenizationError>> {
+    let threads = thread::available_parallelism().map_or(1, usize::from);
+    let chunk_size = sources.len().div_ceil(threads).max(1);
+    thread::scope(|scope| {
+        let handles: Vec<_> = sources
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|source| builder.clone().build(source.chars()).collect())
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|err| panic::resume_unwind(err))
+            })
+            .collect()
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use rstest::rstest;
 
-    use crate::lexer::parsing::{EscapeParseError, NumberParseError, UnicodeParseError};
+    use crate::{
+        lexer::parsing::{EscapeParseError, NumberParseError, UnicodeParseError},
+        vfs::VfsSource,
+    };
+
+    use std::ops::Range;
 
-    use super::{Lexer, TokenizationError, tokens::Token};
+    use super::{
+        Lexer, TokenizationError,
+        builder::LexerBuilder,
+        incremental::LexedSource,
+        lex_many,
+        tokens::{CommentKind, Token},
+    };
 
     fn assert_results<const N: usize>(
         source: &str,
         expected: [Result<Token, TokenizationError>; N],
     ) {
         let tokens: Vec<_> = Lexer::new(source.chars()).collect();
-        assert_eq!(tokens, Vec::from(expected))
+        let mut expected = Vec::from(expected);
+        if !expected.last().is_some_and(Result::is_err) {
+            expected.push(Ok(Token::Eof)); // not emitted after errors
+        }
+        assert_eq!(tokens, expected)
     }
 
     fn assert_tokens<const N: usize>(source: &str, expected: [Token; N]) {
@@ -173,6 +521,7 @@ mod tests {
     #[case("-", Token::Minus)]
     #[case("*", Token::Asterisk)]
     #[case("/", Token::Slash)]
+    #[case("%", Token::Percent)]
     #[case("(", Token::LeftParen)]
     #[case(")", Token::RightParen)]
     #[case("[", Token::LeftSquare)]
@@ -181,6 +530,8 @@ mod tests {
     #[case("}", Token::RightBrace)]
     #[case(",", Token::Comma)]
     #[case(".", Token::Dot)]
+    #[case("..", Token::DoubleDot)]
+    #[case("..=", Token::DoubleDotEqual)]
     #[case("=", Token::Equal)]
     #[case("==", Token::DoubleEqual)]
     #[case("!", Token::Bang)]
@@ -193,6 +544,11 @@ mod tests {
     #[case("&&", Token::DoubleAmpersand)]
     #[case("|", Token::Pipe)]
     #[case("||", Token::DoublePipe)]
+    #[case("+=", Token::PlusEqual)]
+    #[case("-=", Token::MinusEqual)]
+    #[case("*=", Token::AsteriskEqual)]
+    #[case("/=", Token::SlashEqual)]
+    #[case("%=", Token::PercentEqual)]
     #[case("let", Token::Let)]
     #[case("var", Token::Var)]
     #[case("const", Token::Const)]
@@ -203,30 +559,113 @@ mod tests {
     #[case("class", Token::Class)]
     #[case("fn", Token::Fn)]
     #[case("return", Token::Return)]
+    #[case("in", Token::In)]
+    #[case("not", Token::Not)]
     #[case("null", Token::Null)]
     #[case("true", Token::BoolLiteral(true))]
     #[case("false", Token::BoolLiteral(false))]
     #[case("//", Token::LineCommentOpen)]
     #[case("/*", Token::BlockCommentOpen)]
     #[case(";", Token::SemiColon)]
+    #[case("?", Token::QuestionMark)]
+    #[case("?.", Token::QuestionMarkDot)]
+    #[case("??", Token::DoubleQuestionMark)]
+    #[case(":", Token::Colon)]
     fn simple_tokens(#[case] source: &str, #[case] expected: Token) {
         assert_tokens(source, [expected]);
     }
 
+    // the tokens that lex on their own in the normal state, then the other variants.
+    // the match is exhaustive, so a new variant must be added to one of the lists
+    macro_rules! standalone_tokens {
+        ($($token:ident),* ; $($other:pat),*) => {
+            fn standalone_tokens() -> Vec<Token> {
+                let _ = |token: &Token| match token {
+                    $(Token::$token)|* => {}
+                    $($other)|* => {}
+                };
+                vec![$(Token::$token),*]
+            }
+        };
+    }
+
+    standalone_tokens!(
+        Plus, Minus, Asterisk, Slash, Percent, LeftParen, RightParen, LeftSquare, RightSquare,
+        LeftBrace, RightBrace, Comma, Dot, DoubleDot, DoubleDotEqual, Equal, DoubleEqual, Bang,
+        BangEqual, Greater, GreaterEqual, Less, LessEqual, Ampersand, DoubleAmpersand, Pipe,
+        DoublePipe, PlusEqual, MinusEqual, AsteriskEqual, SlashEqual, PercentEqual, StringOpen,
+        TemplateOpen, LineCommentOpen, BlockCommentOpen, SemiColon, QuestionMark, QuestionMarkDot,
+        DoubleQuestionMark, Colon, Let, Var, Const, If, Else, For, While, Class, Fn, Return, In,
+        Not, Null;
+        // only valid inside strings or comments, or carrying a value
+        Token::StringClose,
+        Token::DollarLeftBrace,
+        Token::CommentClose(_),
+        Token::BoolLiteral(_),
+        Token::Identifier(_),
+        Token::RawIdentifier(_),
+        Token::IntLiteral(_),
+        Token::FloatLiteral(_),
+        Token::StringLiteral(_),
+        Token::CommentLiteral(_),
+        Token::CustomKeyword(_),
+        Token::Unknown(_),
+        Token::Comment { .. },
+        Token::String(_),
+        Token::Whitespace(_),
+        Token::Eof
+    );
+
+    // the source text of a token is its Display
+    #[rstest]
+    fn simple_tokens_conformance() {
+        for token in standalone_tokens() {
+            let source = token.to_string();
+            assert_tokens(&source, [token]);
+        }
+    }
+
     #[rstest]
     #[case("foo", Token::Identifier("foo".to_string()))]
     #[case("_123", Token::Identifier("_123".to_string()))]
+    #[case("überσ_2", Token::Identifier("überσ_2".to_string()))]
+    #[case("变量", Token::Identifier("变量".to_string()))]
+    #[case("a\u{300}", Token::Identifier("à".to_string()))] // normalized to NFC
+    #[case("r#let", Token::RawIdentifier("let".to_string()))]
+    #[case("r#foo", Token::RawIdentifier("foo".to_string()))]
+    #[case("r#r", Token::RawIdentifier("r".to_string()))]
+    #[case("r", Token::Identifier("r".to_string()))]
     #[case("123", Token::IntLiteral(123))]
-    #[case("-123", Token::IntLiteral(-123))]
     #[case("123.456", Token::FloatLiteral(123.456))]
-    #[case("-123.456", Token::FloatLiteral(-123.456))]
+    #[case("1.2e-3", Token::FloatLiteral(0.0012))]
+    #[case("1E+10", Token::FloatLiteral(1e10))]
+    #[case("1e5", Token::FloatLiteral(100000.0))]
+    #[case("0x1e5", Token::IntLiteral(0x1e5))]
     fn normal_literals(#[case] source: &str, #[case] expected: Token) {
         assert_tokens(source, [expected]);
     }
 
+    // the sign is a separate token, see Token::int_value
+    #[rstest]
+    #[case("-123", [Token::Minus, Token::IntLiteral(123)])]
+    #[case(
+        "-9223372036854775808",
+        [Token::Minus, Token::IntLiteral(9223372036854775808)]
+    )]
+    #[case("-2.5e3", [Token::Minus, Token::FloatLiteral(2500.0)])]
+    #[case("1-2", [Token::IntLiteral(1), Token::Minus, Token::IntLiteral(2)])]
+    #[case(
+        "1 - -2",
+        [Token::IntLiteral(1), Token::Minus, Token::Minus, Token::IntLiteral(2)]
+    )]
+    #[case("-x", [Token::Minus, Token::Identifier("x".to_string())])]
+    fn signed_literals<const N: usize>(#[case] source: &str, #[case] expected: [Token; N]) {
+        assert_tokens(source, expected);
+    }
+
     #[rstest]
-    #[case("123.abc")]
     #[case("123.456.789")]
+    #[case("123.4abc")]
     fn floats_infinite_points(#[case] source: &str) {
         assert_results(
             source,
@@ -316,6 +755,54 @@ mod tests {
             Token::StringClose,
         ]
     )]
+    #[case(
+        r#"template"hi ${name}""#, 
+        [
+            Token::TemplateOpen,
+            Token::StringLiteral("hi ".to_string()), 
+            Token::DollarLeftBrace,
+            Token::Identifier("name".to_string()),
+            Token::RightBrace,
+            Token::StringClose,
+        ]
+    )]
+    #[case(
+        r#"template "a""#, 
+        [
+            Token::Identifier("template".to_string()),
+            Token::StringOpen,
+            Token::StringLiteral("a".to_string()), 
+            Token::StringClose,
+        ]
+    )]
+    #[case(
+        r#""\0""#,
+        [Token::StringOpen, Token::StringLiteral("\0".to_string()), Token::StringClose]
+    )] // escaped NUL is not the end of input
+    #[case(
+        r#""say \"hi\" \'x\'""#,
+        [
+            Token::StringOpen,
+            Token::StringLiteral("say ".to_string()),
+            Token::StringLiteral("\"".to_string()),
+            Token::StringLiteral("hi".to_string()),
+            Token::StringLiteral("\"".to_string()),
+            Token::StringLiteral(" ".to_string()),
+            Token::StringLiteral("'".to_string()),
+            Token::StringLiteral("x".to_string()),
+            Token::StringLiteral("'".to_string()),
+            Token::StringClose,
+        ]
+    )]
+    #[case(
+        r#""\x41\x7a""#,
+        [
+            Token::StringOpen,
+            Token::StringLiteral("A".to_string()),
+            Token::StringLiteral("z".to_string()),
+            Token::StringClose,
+        ]
+    )]
     fn string_literals<const N: usize>(#[case] source: &str, #[case] expected: [Token; N]) {
         assert_tokens(source, expected);
     }
@@ -333,12 +820,27 @@ mod tests {
     fn raw_string_literals<const N: usize>(#[case] source: &str, #[case] expected: [Token; N]) {
         assert_tokens(source, expected);
     }
+    #[rstest]
+    #[case("<<~END\n  foo\n    bar\n  END", [Token::StringOpen, Token::StringLiteral("foo\n  bar".to_string()), Token::StringClose])]
+    #[case("<<~END\r\n  foo\r\n  END", [Token::StringOpen, Token::StringLiteral("foo".to_string()), Token::StringClose])]
+    #[case("<<~END\nfoo\n\n  bar\n  END", [Token::StringOpen, Token::StringLiteral("foo\n\nbar".to_string()), Token::StringClose])]
+    #[case("<<~END\nEND", [Token::StringOpen, Token::StringClose])]
+    #[case("<<~END\n  ENDING\n  END", [Token::StringOpen, Token::StringLiteral("ENDING".to_string()), Token::StringClose])]
+    #[case("<<~SQL\n    select ${a}\n    SQL;", [Token::StringOpen, Token::StringLiteral("select ${a}".to_string()), Token::StringClose, Token::SemiColon])]
+    #[case("<<~END\n  foo", [Token::StringOpen, Token::StringLiteral("  foo".to_string())])]
+    #[case("<<~END \t\n  foo\n  END", [Token::StringOpen, Token::StringLiteral("foo".to_string()), Token::StringClose])] // trailing whitespace after the delimiter
+    #[case("<<~END \r\n  foo\r\n  END", [Token::StringOpen, Token::StringLiteral("foo".to_string()), Token::StringClose])]
+    #[case("a <<b", [Token::Identifier("a".to_string()), Token::Less, Token::Less, Token::Identifier("b".to_string())])]
+    fn heredoc_literals<const N: usize>(#[case] source: &str, #[case] expected: [Token; N]) {
+        assert_tokens(source, expected);
+    }
+
     #[rstest]
     #[case("// foo", [Token::LineCommentOpen, Token::CommentLiteral(" foo".to_string())])]
-    #[case("// foo\n", [Token::LineCommentOpen, Token::CommentLiteral(" foo".to_string()), Token::CommentClose])]
+    #[case("// foo\n", [Token::LineCommentOpen, Token::CommentLiteral(" foo".to_string()), Token::CommentClose(CommentKind::Line)])]
     #[case("// foo */", [Token::LineCommentOpen, Token::CommentLiteral(" foo */".to_string())])]
     #[case("/* foo", [Token::BlockCommentOpen, Token::CommentLiteral(" foo".to_string())])]
-    #[case("/* foo */", [Token::BlockCommentOpen, Token::CommentLiteral(" foo ".to_string()), Token::CommentClose])]
+    #[case("/* foo */", [Token::BlockCommentOpen, Token::CommentLiteral(" foo ".to_string()), Token::CommentClose(CommentKind::Block)])]
     #[case("/* foo\n", [Token::BlockCommentOpen, Token::CommentLiteral(" foo\n".to_string())])]
     fn comments<const N: usize>(#[case] source: &str, #[case] expected: [Token; N]) {
         assert_tokens(source, expected);
@@ -348,11 +850,32 @@ mod tests {
     #[case("@", [Err(TokenizationError::InvalidToken("@".to_string()))])]
     #[case("@a", [Err(TokenizationError::InvalidToken("@".to_string()))])] // everything ignored after error
     #[case("${", [Err(TokenizationError::InvalidToken("$".to_string()))])] // invalid outside string literal
-    #[case(r#""\ ""#, [Ok(Token::StringOpen), Err(TokenizationError::NoEscape)])]
+    #[case("a€", [Ok(Token::Identifier("a".to_string())), Err(TokenizationError::InvalidToken("€".to_string()))])] // multibyte char
+    #[case("٣a", [Err(TokenizationError::InvalidToken("٣".to_string()))])] // digits are not XID_Start
+    #[case("ª·", [Ok(Token::Identifier("ª·".to_string()))])] // · is only XID_Continue
+    #[case("a😀", [Ok(Token::Identifier("a".to_string())), Err(TokenizationError::InvalidToken("😀".to_string()))])]
+    #[case(r#""\ ""#, [Ok(Token::StringOpen), Err(TokenizationError::NoEscape(1..3))])]
+    #[case(r#""a\"#, [Ok(Token::StringOpen), Ok(Token::StringLiteral("a".to_string())), Err(TokenizationError::NoEscape(2..3))])]
     // all possible parsing errors are tested in the parsing.rs file, only proper error propagation is tested here
-    #[case(r#""\a""#, [Ok(Token::StringOpen), Err(TokenizationError::EscapeParse(EscapeParseError::InvalidEscape(r"\a".to_string())))])]
-    #[case(r#""\u{g}""#, [Ok(Token::StringOpen), Err(TokenizationError::UnicodeParse(UnicodeParseError::InvalidHex("g".to_string())))])]
+    #[case(r#""\a""#, [Ok(Token::StringOpen), Err(TokenizationError::EscapeParse(EscapeParseError::InvalidEscape(r"\a".to_string()), 1..3))])]
+    #[case(r#""\xg1""#, [Ok(Token::StringOpen), Err(TokenizationError::EscapeParse(EscapeParseError::InvalidHex("g1".to_string()), 1..5))])]
+    #[case(r#""\xFF""#, [Ok(Token::StringOpen), Err(TokenizationError::EscapeParse(EscapeParseError::HexOutOfRange("FF".to_string()), 1..5))])]
+    #[case(r#""\x4""#, [Ok(Token::StringOpen), Err(TokenizationError::EscapeParse(EscapeParseError::NoValue(r"\x".to_string()), 1..3))])]
+    #[case(r#""\u{g}""#, [Ok(Token::StringOpen), Err(TokenizationError::UnicodeParse(UnicodeParseError::InvalidHex("g".to_string()), 1..6))])]
+    #[case(r#""ab\é""#, [Ok(Token::StringOpen), Ok(Token::StringLiteral("ab".to_string())), Err(TokenizationError::EscapeParse(EscapeParseError::InvalidEscape(r"\é".to_string()), 3..6))])]
     #[case("0a123", [Err(TokenizationError::NumberParse(NumberParseError::InvalidRadix("a".to_string())))])]
+    #[case("1.5e", [Err(TokenizationError::NumberParse(NumberParseError::InvalidExponent("e".to_string())))])]
+    #[case("1e+x", [Err(TokenizationError::NumberParse(NumberParseError::InvalidExponent("e+x".to_string())))])]
+    #[case("0x1.5", [Err(TokenizationError::NumberParse(NumberParseError::RadixFloat("x".to_string())))])]
+    #[case("####x", [Err(TokenizationError::UnterminatedRawStringOpener(0..4))])]
+    #[case("a #", [Ok(Token::Identifier("a".to_string())), Err(TokenizationError::UnterminatedRawStringOpener(2..3))])]
+    #[case("<<~END", [Err(TokenizationError::UnterminatedHeredocOpener(0..6))])]
+    #[case("a <<~END b\n", [Ok(Token::Identifier("a".to_string())), Err(TokenizationError::UnterminatedHeredocOpener(2..8))])] // only whitespace after the delimiter
+    #[case("<<~", [Err(TokenizationError::UnterminatedHeredocOpener(0..3))])]
+    #[case("\0", [Err(TokenizationError::NulCharacter(0..1))])]
+    #[case("a\0b", [Ok(Token::Identifier("a".to_string())), Err(TokenizationError::NulCharacter(1..2))])]
+    #[case("a\n \0", [Ok(Token::Identifier("a".to_string())), Err(TokenizationError::NulCharacter(3..4))])]
+    #[case("\"a\0\"", [Ok(Token::StringOpen), Ok(Token::StringLiteral("a".to_string())), Err(TokenizationError::NulCharacter(2..3))])]
     fn errors<const N: usize>(
         #[case] source: &str,
         #[case] expected: [Result<Token, TokenizationError>; N],
@@ -423,6 +946,171 @@ mod tests {
             Token::Identifier("attribute".to_string()),
         ]
     )]
+    #[case(
+        "5.attribute", 
+        [
+            Token::IntLiteral(5),
+            Token::Dot,
+            Token::Identifier("attribute".to_string()),
+        ]
+    )]
+    #[case(
+        "-5.to_string()", 
+        [
+            Token::Minus,
+            Token::IntLiteral(5),
+            Token::Dot,
+            Token::Identifier("to_string".to_string()),
+            Token::LeftParen,
+            Token::RightParen,
+        ]
+    )]
+    #[case(
+        "123.abc", 
+        [
+            Token::IntLiteral(123),
+            Token::Dot,
+            Token::Identifier("abc".to_string()),
+        ]
+    )]
+    #[case(
+        "5.",
+        [
+            Token::IntLiteral(5),
+            Token::Dot,
+        ]
+    )]
+    #[case(
+        "5.6.attribute", 
+        [
+            Token::FloatLiteral(5.6),
+            Token::Dot,
+            Token::Identifier("attribute".to_string()),
+        ]
+    )]
+    #[case(
+        "a+=-1", 
+        [
+            Token::Identifier("a".to_string()),
+            Token::PlusEqual,
+            Token::Minus,
+            Token::IntLiteral(1),
+        ]
+    )]
+    #[case(
+        "a-=1/=2", 
+        [
+            Token::Identifier("a".to_string()),
+            Token::MinusEqual,
+            Token::IntLiteral(1),
+            Token::SlashEqual,
+            Token::IntLiteral(2),
+        ]
+    )]
+    #[case(
+        "0..10", 
+        [
+            Token::IntLiteral(0),
+            Token::DoubleDot,
+            Token::IntLiteral(10),
+        ]
+    )]
+    #[case(
+        "-1..=a", 
+        [
+            Token::Minus,
+            Token::IntLiteral(1),
+            Token::DoubleDotEqual,
+            Token::Identifier("a".to_string()),
+        ]
+    )]
+    #[case(
+        "0.5..1.5", 
+        [
+            Token::FloatLiteral(0.5),
+            Token::DoubleDot,
+            Token::FloatLiteral(1.5),
+        ]
+    )]
+    #[case(
+        "a...b", 
+        [
+            Token::Identifier("a".to_string()),
+            Token::DoubleDot,
+            Token::Dot,
+            Token::Identifier("b".to_string()),
+        ]
+    )]
+    #[case(
+        "a not in b", 
+        [
+            Token::Identifier("a".to_string()),
+            Token::Not,
+            Token::In,
+            Token::Identifier("b".to_string()),
+        ]
+    )]
+    #[case(
+        "inner in nothing", 
+        [
+            Token::Identifier("inner".to_string()),
+            Token::In,
+            Token::Identifier("nothing".to_string()),
+        ]
+    )]
+    #[case(
+        "a||b?1:-2.5", 
+        [
+            Token::Identifier("a".to_string()),
+            Token::DoublePipe,
+            Token::Identifier("b".to_string()),
+            Token::QuestionMark,
+            Token::IntLiteral(1),
+            Token::Colon,
+            Token::Minus,
+            Token::FloatLiteral(2.5),
+        ]
+    )]
+    #[case(
+        "a?.b ?? c???.d",
+        [
+            Token::Identifier("a".to_string()),
+            Token::QuestionMarkDot,
+            Token::Identifier("b".to_string()),
+            Token::DoubleQuestionMark,
+            Token::Identifier("c".to_string()),
+            Token::DoubleQuestionMark,
+            Token::QuestionMarkDot,
+            Token::Identifier("d".to_string()),
+        ]
+    )]
+    #[case(
+        "r#`a`#", // not a raw identifier
+        [
+            Token::Identifier("r".to_string()),
+            Token::StringOpen,
+            Token::StringLiteral("a".to_string()),
+            Token::StringClose,
+        ]
+    )]
+    #[case(
+        "r#fn.r#in",
+        [
+            Token::RawIdentifier("fn".to_string()),
+            Token::Dot,
+            Token::RawIdentifier("in".to_string()),
+        ]
+    )]
+    #[case(
+        "a?.5:b",
+        [
+            Token::Identifier("a".to_string()),
+            Token::QuestionMarkDot,
+            Token::IntLiteral(5),
+            Token::Colon,
+            Token::Identifier("b".to_string()),
+        ]
+    )]
     #[case(
         ".!<=+", 
         [
@@ -435,4 +1123,46 @@ mod tests {
     fn free_text<const N: usize>(#[case] source: &str, #[case] expected: [Token; N]) {
         assert_tokens(source, expected);
     }
+
+    #[rstest]
+    #[case("let  é = 1", [0..3, 5..7, 8..9, 10..11, 11..11])]
+    #[case("\"a${b}\"", [0..1, 1..2, 2..4, 4..5, 5..6, 6..7, 7..7])]
+    #[case("// a\n1 ", [0..2, 2..4, 4..5, 5..6, 7..7])]
+    fn spans<const N: usize>(#[case] source: &str, #[case] expected: [Range<usize>; N]) {
+        let mut lexer = Lexer::new(source.chars());
+        let mut spans = vec![];
+        while lexer.next().is_some() {
+            spans.push(lexer.span().range());
+        }
+        assert_eq!(Vec::from(expected), spans);
+    }
+
+    #[rstest]
+    fn many_sources() {
+        let mut vfs = VfsSource::new();
+        for i in 0..20 {
+            vfs.add(&format!("{:02}.doot", i), &format!("a{}", i));
+        }
+        vfs.add("bad.doot", "a @");
+        let sources: Vec<_> = vfs.files().cloned().collect();
+        let results = lex_many(&sources, &LexerBuilder::new());
+        assert_eq!(21, results.len());
+        for (i, result) in results[..20].iter().enumerate() {
+            assert_eq!(
+                &Ok(vec![Token::Identifier(format!("a{}", i)), Token::Eof]),
+                result
+            );
+        }
+        assert_eq!(
+            Err(TokenizationError::InvalidToken("@".to_string())),
+            results[20]
+        );
+    }
+
+    #[rstest]
+    fn lexer_is_send() {
+        fn assert_send<T: Send>() {}
+        assert_send::<Lexer<'static>>();
+        assert_send::<LexedSource>();
+    }
 }
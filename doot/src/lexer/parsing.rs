@@ -12,12 +12,16 @@ pub enum NumberParseError {
     PositiveOverflow,
     NegativeOverflow,
     InvalidFloat,
+    InvalidExponent(String),
+    RadixFloat(String),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum EscapeParseError {
     NoValue(String),
     InvalidEscape(String),
+    InvalidHex(String),    // \x escape with non hex digits
+    HexOutOfRange(String), // \x escape above 7F
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -35,6 +39,10 @@ impl Display for NumberParseError {
             NumberParseError::PositiveOverflow => "positive overflow".to_string(),
             NumberParseError::NegativeOverflow => "negative overflow".to_string(),
             NumberParseError::InvalidFloat => "invalid float".to_string(),
+            NumberParseError::InvalidExponent(exponent) => {
+                format!("invalid float exponent {}", exponent)
+            }
+            NumberParseError::RadixFloat(radix) => format!("invalid float radix {}", radix),
         }
         .fmt(f)
     }
@@ -49,7 +57,9 @@ pub(super) fn escape(source: &str) -> Result<char, EscapeParseError> {
         r"\\" => Ok('\\'),
         r"\0" => Ok('\0'),
         r"\$" => Ok('$'), // escape \$ because of ${ in strings
-        c if c == r"\u" => Err(EscapeParseError::NoValue(c.to_string())),
+        r#"\""# => Ok('"'),
+        r"\'" => Ok('\''),
+        c if c == r"\u" || c == r"\x" => Err(EscapeParseError::NoValue(c.to_string())),
         source => Err(EscapeParseError::InvalidEscape(source.to_string())),
     }
 }
@@ -64,10 +74,20 @@ pub(super) fn parse_unicode(hex_string: &str) -> Result<char, UnicodeParseError>
     )
 }
 
+// the two hex digits of a \x escape, ascii only like in rust
+pub(super) fn parse_hex_escape(hex_string: &str) -> Result<char, EscapeParseError> {
+    if hex_string.len() != 2 || !hex_string.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(EscapeParseError::InvalidHex(hex_string.to_string()));
+    }
+    match u8::from_str_radix(hex_string, 16) {
+        Ok(value) if value.is_ascii() => Ok(value as char),
+        _ => Err(EscapeParseError::HexOutOfRange(hex_string.to_string())),
+    }
+}
+
 fn map_int_error(err: ParseIntError) -> NumberParseError {
     match err.kind() {
-        IntErrorKind::Empty => panic!(),
-        IntErrorKind::InvalidDigit => NumberParseError::InvalidInt,
+        IntErrorKind::Empty | IntErrorKind::InvalidDigit => NumberParseError::InvalidInt, // 0x is empty
         IntErrorKind::PosOverflow => NumberParseError::PositiveOverflow,
         IntErrorKind::NegOverflow => NumberParseError::NegativeOverflow,
         IntErrorKind::Zero => panic!(),
@@ -83,19 +103,10 @@ fn clean_source(source: &str) -> String {
     source.replace("_", "")
 }
 
-pub(super) fn parse_int(source: &str) -> Result<i64, NumberParseError> {
-    let cleaned = clean_source(source);
-    let mut source = cleaned.as_str();
-    let mut chars = source.chars().peekable();
-    let sign = match chars.peek().cloned() {
-        Some(c) if c == '+' || c == '-' => {
-            chars.next();
-            source = &source[1..];
-            c
-        }
-        Some('0'..='9') => '+',
-        Some(_) | None => return Err(NumberParseError::InvalidInt),
-    };
+// the magnitude of an int literal, the sign is a separate token
+pub(super) fn parse_int(source: &str) -> Result<u64, NumberParseError> {
+    let source = clean_source(source);
+    let mut chars = source.chars();
     match chars.next() {
         Some('0') => {
             if let Some(radix) = chars.next() {
@@ -107,38 +118,55 @@ pub(super) fn parse_int(source: &str) -> Result<i64, NumberParseError> {
                     _ => Err(NumberParseError::InvalidRadix(radix.to_string())),
                 }
                 .and_then(|radix| {
-                    i64::from_str_radix(
-                        &format!(
-                            "{}{}",
-                            sign,
-                            source[if radix == 10 { 0 } else { 2 }..].to_string()
-                        ),
-                        radix,
-                    )
-                    .map_err(map_int_error)
+                    u64::from_str_radix(&source[if radix == 10 { 0 } else { 2 }..], radix)
+                        .map_err(map_int_error)
                 })
             } else {
                 Ok(0)
             }
         }
-        Some('1'..='9') => format!("{}{}", sign, source).parse().map_err(map_int_error),
+        Some('1'..='9') => source.parse().map_err(map_int_error),
         Some(_) | None => Err(NumberParseError::InvalidInt),
     }
 }
 
+// the value of an int literal with its sign folded in, i64::MIN is the only one
+// whose magnitude does not fit in an i64
+pub(super) fn fold_sign(magnitude: u64, negative: bool) -> Result<i64, NumberParseError> {
+    if negative {
+        0i64.checked_sub_unsigned(magnitude)
+            .ok_or(NumberParseError::NegativeOverflow)
+    } else {
+        i64::try_from(magnitude).map_err(|_| NumberParseError::PositiveOverflow)
+    }
+}
+
 pub(super) fn parse_float(source: &str) -> Result<f64, NumberParseError> {
-    clean_source(source)
-        .parse()
-        .map_err(map_float_error)
-        .and_then(|val| {
-            if val > f64::MAX {
-                Err(NumberParseError::PositiveOverflow)
-            } else if val < -f64::MAX {
-                Err(NumberParseError::NegativeOverflow)
-            } else {
-                Ok(val)
-            }
-        })
+    let cleaned = clean_source(source);
+    let unsigned = cleaned.strip_prefix(['+', '-']).unwrap_or(cleaned.as_str());
+    if let Some(radix) = ["0x", "0b", "0o"]
+        .into_iter()
+        .find(|prefix| unsigned.starts_with(prefix))
+    {
+        return Err(NumberParseError::RadixFloat(radix[1..].to_string()));
+    }
+    if let Some(exponent) = unsigned.find(['e', 'E']).map(|i| &unsigned[i..]) {
+        let value = exponent[1..]
+            .strip_prefix(['+', '-'])
+            .unwrap_or(&exponent[1..]);
+        if value.is_empty() || !value.chars().all(|c| c.is_ascii_digit()) {
+            return Err(NumberParseError::InvalidExponent(exponent.to_string()));
+        }
+    }
+    cleaned.parse().map_err(map_float_error).and_then(|val| {
+        if val > f64::MAX {
+            Err(NumberParseError::PositiveOverflow)
+        } else if val < -f64::MAX {
+            Err(NumberParseError::NegativeOverflow)
+        } else {
+            Ok(val)
+        }
+    })
 }
 
 #[cfg(test)]
@@ -148,8 +176,8 @@ mod tests {
     use rstest::rstest;
 
     use super::{
-        EscapeParseError, NumberParseError, UnicodeParseError, escape, parse_float, parse_int,
-        parse_unicode,
+        EscapeParseError, NumberParseError, UnicodeParseError, escape, fold_sign, parse_float,
+        parse_hex_escape, parse_int, parse_unicode,
     };
 
     #[rstest]
@@ -159,6 +187,8 @@ mod tests {
     #[case(r"\\", '\\')]
     #[case(r"\0", '\0')]
     #[case(r"\$", '$')]
+    #[case(r#"\""#, '"')]
+    #[case(r"\'", '\'')]
     fn escape_ok(#[case] source: &str, #[case] expected: char) {
         let result = escape(source);
         assert!(result.is_ok());
@@ -167,6 +197,7 @@ mod tests {
 
     #[rstest]
     #[case(r"\u", EscapeParseError::NoValue(r"\u".to_string()))]
+    #[case(r"\x", EscapeParseError::NoValue(r"\x".to_string()))]
     #[case(r"\a", EscapeParseError::InvalidEscape(r"\a".to_string()))]
     fn escape_fail(#[case] source: &str, #[case] expected: EscapeParseError) {
         let result = escape(source);
@@ -174,6 +205,23 @@ mod tests {
         assert_eq!(expected, result.unwrap_err());
     }
 
+    #[rstest]
+    #[case("41", 'A')]
+    #[case("0a", '\n')]
+    #[case("7F", '\u{7F}')] // maximum
+    fn hex_escape_ok(#[case] source: &str, #[case] expected: char) {
+        assert_eq!(Ok(expected), parse_hex_escape(source));
+    }
+
+    #[rstest]
+    #[case("4g", EscapeParseError::InvalidHex("4g".to_string()))]
+    #[case("4", EscapeParseError::InvalidHex("4".to_string()))]
+    #[case("+4", EscapeParseError::InvalidHex("+4".to_string()))]
+    #[case("80", EscapeParseError::HexOutOfRange("80".to_string()))] // not ascii
+    fn hex_escape_fail(#[case] source: &str, #[case] expected: EscapeParseError) {
+        assert_eq!(Err(expected), parse_hex_escape(source));
+    }
+
     #[rstest]
     #[case("0", '\0')] // null
     #[case("41", 'A')] // ascii
@@ -199,24 +247,18 @@ mod tests {
 
     #[rstest]
     #[case("0", 0)]
-    #[case("-0", 0)]
     #[case("123", 123)]
-    #[case("-123", -123)]
     #[case("00123", 123)]
-    #[case("-00123", -123)]
-    #[case(&i64::MAX.to_string(), i64::MAX)]
-    #[case(&(i64::MIN).to_string(), i64::MIN)]
+    #[case(&u64::MAX.to_string(), u64::MAX)]
+    #[case("9223372036854775808", i64::MIN.unsigned_abs())]
     #[case("0b1100", 12)] // binary
-    #[case("-0b1100", -12)] // binary
     #[case("0o074", 60)] // octal
-    #[case("-0o074", -60)] // octal
     #[case("0xFFAA", 65450)] // hex
-    #[case("-0xFFAA", -65450)] // hex
     #[case("0xffaa", 65450)] // hex
     #[case("_1", 1)]
     #[case("1_", 1)]
     #[case("1___2_", 12)]
-    fn int_ok(#[case] source: &str, #[case] expected: i64) {
+    fn int_ok(#[case] source: &str, #[case] expected: u64) {
         let result = parse_int(source);
         assert!(result.is_ok());
         assert_eq!(expected, result.unwrap());
@@ -224,29 +266,39 @@ mod tests {
 
     #[rstest]
     #[case("abc", NumberParseError::InvalidInt)]
-    #[case("-abc", NumberParseError::InvalidInt)]
+    #[case("-123", NumberParseError::InvalidInt)] // the sign is a separate token
     #[case("abc123", NumberParseError::InvalidInt)]
-    #[case("-abc123", NumberParseError::InvalidInt)]
+    #[case("0x", NumberParseError::InvalidInt)]
     #[case("0a123", NumberParseError::InvalidRadix("a".to_string()))]
-    #[case("-0a123", NumberParseError::InvalidRadix("a".to_string()))]
     #[case("0abc123", NumberParseError::InvalidRadix("a".to_string()))]
-    #[case("-0abc123", NumberParseError::InvalidRadix("a".to_string()))]
-    #[case(&(i64::MAX as i128 + 1).to_string(), NumberParseError::PositiveOverflow)]
-    #[case(&(i64::MIN as i128 - 1).to_string(), NumberParseError::NegativeOverflow)]
+    #[case(&(u64::MAX as i128 + 1).to_string(), NumberParseError::PositiveOverflow)]
     #[case(
         "0xFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF",
         NumberParseError::PositiveOverflow
     )]
-    #[case(
-        "-0xFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF",
-        NumberParseError::NegativeOverflow
-    )]
     fn int_fail(#[case] source: &str, #[case] expected: NumberParseError) {
         let result = parse_int(source);
         assert!(result.is_err());
         assert_eq!(expected, result.unwrap_err());
     }
 
+    #[rstest]
+    #[case(0, true, Ok(0))]
+    #[case(5, false, Ok(5))]
+    #[case(5, true, Ok(-5))]
+    #[case(i64::MAX as u64, false, Ok(i64::MAX))]
+    #[case(i64::MIN.unsigned_abs(), true, Ok(i64::MIN))]
+    #[case(i64::MIN.unsigned_abs(), false, Err(NumberParseError::PositiveOverflow))]
+    #[case(i64::MIN.unsigned_abs() + 1, true, Err(NumberParseError::NegativeOverflow))]
+    #[case(u64::MAX, true, Err(NumberParseError::NegativeOverflow))]
+    fn sign_folding(
+        #[case] magnitude: u64,
+        #[case] negative: bool,
+        #[case] expected: Result<i64, NumberParseError>,
+    ) {
+        assert_eq!(expected, fold_sign(magnitude, negative));
+    }
+
     #[rstest]
     #[case("123.45", 123.45)]
     #[case("-123.45", -123.45)]
@@ -256,6 +308,10 @@ mod tests {
     #[case("_123.45", 123.45)]
     #[case("123__.45", 123.45)]
     #[case("1.2e3", 1200.0)]
+    #[case("1.2e-3", 0.0012)]
+    #[case("1E+10", 1e10)]
+    #[case("-1e5", -100000.0)]
+    #[case("1_0e1_0", 10e10)]
     #[case(&(f64::MAX).to_string(), f64::MAX)]
     #[case(&(-f64::MAX).to_string(), -f64::MAX)]
     #[case(&(f64::MIN).to_string(), f64::MIN)]
@@ -268,6 +324,14 @@ mod tests {
 
     #[rstest]
     #[case("abc", NumberParseError::InvalidFloat)]
+    #[case("1.5x", NumberParseError::InvalidFloat)]
+    #[case("1e", NumberParseError::InvalidExponent("e".to_string()))]
+    #[case("1.5E+", NumberParseError::InvalidExponent("E+".to_string()))]
+    #[case("1e5x", NumberParseError::InvalidExponent("e5x".to_string()))]
+    #[case("1e2.5", NumberParseError::InvalidExponent("e2.5".to_string()))]
+    #[case("0x1.5", NumberParseError::RadixFloat("x".to_string()))]
+    #[case("-0b1.0", NumberParseError::RadixFloat("b".to_string()))]
+    #[case("0o1e5", NumberParseError::RadixFloat("o".to_string()))]
     #[case(&(f64::MAX * 2.0).to_string(), NumberParseError::PositiveOverflow)]
     #[case(&(-f64::MAX * 2.0).to_string(), NumberParseError::NegativeOverflow)]
     fn float_fail(#[case] source: &str, #[case] expected: NumberParseError) {
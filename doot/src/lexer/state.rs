@@ -1,11 +1,17 @@
 use std::collections::LinkedList;
 
-use crate::lexer::{matchers::ChainMatcher, parsing};
+use unicode_normalization::{IsNormalized, UnicodeNormalization, is_nfc_quick};
+
+use crate::lexer::{
+    matchers::{ChainMatcher, MatcherState},
+    parsing,
+};
 
 use super::{
     TokenizationError,
+    builder::LexerOptions,
     matchers::{DefaultMatcher, Matcher},
-    tokens::Token,
+    tokens::{CommentKind, Token},
 };
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -13,25 +19,23 @@ pub enum LexerState {
     Normal(bool),
     CompositeString,
     RawString(usize),
-    Comment(String),
+    Heredoc(String),
+    Comment(CommentKind, String), // and its terminator
 }
 
 impl LexerState {
     pub(super) fn ignore_whitespace(&self) -> bool {
-        match self {
-            LexerState::Normal(_) => true,
-            _ => false,
-        }
+        matches!(self, LexerState::Normal(_))
     }
 
-    pub(super) fn matchers(&self) -> Vec<Box<dyn Matcher<Token>>> {
+    pub(super) fn matchers(&self, options: &LexerOptions) -> Vec<Box<dyn Matcher<Token>>> {
         fn int_literal(prefix: &str) -> Box<dyn Matcher<Token>> {
             ChainMatcher::new(
                 [
                     DefaultMatcher::fixed_text(prefix),
                     DefaultMatcher::take_while(
                         |buff, ch| {
-                            if buff.len() == 1 {
+                            if buff.len() == ch.len_utf8() {
                                 ch.is_ascii_digit()
                             } else {
                                 ch == '_' || ch.is_alphanumeric()
@@ -50,19 +54,58 @@ impl LexerState {
         }
 
         fn float_literal(prefix: &str) -> Box<dyn Matcher<Token>> {
+            enum FloatPart {
+                Start,
+                Integer,
+                Radix, // not valid in floats, kept to report the error
+                Point, // a point must be followed by a digit, otherwise it's not part of the float
+                Fraction,
+                Exponent,
+                ExponentSign,
+                ExponentValue,
+            }
+            let mut part = FloatPart::Start;
             ChainMatcher::new(
                 [
                     DefaultMatcher::fixed_text(prefix),
-                    DefaultMatcher::take_while(
-                        |buff, ch| {
-                            if buff.len() == 1 {
-                                ch.is_ascii_digit()
-                            } else {
-                                ch == '_' || ch == '.' || ch.is_alphanumeric()
+                    DefaultMatcher::custom(
+                        MatcherState::Open,
+                        move |buff, ch| {
+                            let alphanumeric = ch == '_' || ch.is_alphanumeric();
+                            part = match (&part, ch) {
+                                (FloatPart::Start, '0'..='9') => FloatPart::Integer,
+                                (FloatPart::Integer, '0'..='9' | '_') => FloatPart::Integer,
+                                (FloatPart::Integer, 'x' | 'b' | 'o') if buff.len() == 2 => {
+                                    FloatPart::Radix
+                                }
+                                (FloatPart::Radix, _) if alphanumeric => FloatPart::Radix,
+                                (
+                                    FloatPart::Integer | FloatPart::Radix | FloatPart::Fraction,
+                                    '.',
+                                ) => FloatPart::Point,
+                                (FloatPart::Point, '0'..='9') => FloatPart::Fraction,
+                                (FloatPart::Integer | FloatPart::Fraction, 'e' | 'E') => {
+                                    FloatPart::Exponent
+                                }
+                                (FloatPart::Fraction, _) if alphanumeric => FloatPart::Fraction,
+                                (FloatPart::Exponent, '+' | '-') => FloatPart::ExponentSign,
+                                (
+                                    FloatPart::Exponent
+                                    | FloatPart::ExponentSign
+                                    | FloatPart::ExponentValue,
+                                    _,
+                                ) if alphanumeric => FloatPart::ExponentValue,
+                                _ => return MatcherState::Broken,
+                            };
+                            match part {
+                                FloatPart::Start
+                                | FloatPart::Integer
+                                | FloatPart::Radix
+                                | FloatPart::Point => MatcherState::Open,
+                                _ => MatcherState::Closeable,
                             }
                         },
-                        1,
-                        |value, _| Ok(value.to_string()),
+                        |value, _| Ok((value.to_string(), value.len())),
                     ),
                 ],
                 |val, _, _| {
@@ -72,97 +115,225 @@ impl LexerState {
                 },
             )
         }
-        match *self {
-            Self::Normal(from_string) => vec![
-                // symbols
-                DefaultMatcher::simple_text("+", Token::Plus),
-                DefaultMatcher::simple_text("-", Token::Minus),
-                DefaultMatcher::simple_text("*", Token::Asterisk),
-                DefaultMatcher::simple_text("/", Token::Slash),
-                DefaultMatcher::simple_text("(", Token::LeftParen),
-                DefaultMatcher::simple_text(")", Token::RightParen),
-                DefaultMatcher::simple_text("[", Token::LeftSquare),
-                DefaultMatcher::simple_text("]", Token::RightSquare),
-                DefaultMatcher::text("{", move |_, state| {
-                    if from_string {
-                        state.push(Self::Normal(true));
-                    }
-                    Ok(Token::LeftBrace)
-                }),
-                DefaultMatcher::text("}", move |_, state| {
-                    if from_string {
-                        state.pop();
+
+        // UAX #31 identifiers, normalized to NFC so that equal names compare equal
+        fn identifier<'a, T: Clone + Send + 'a>(
+            options: &LexerOptions,
+            token: fn(String) -> T,
+        ) -> Box<dyn Matcher<T> + 'a> {
+            let max_identifier_length = options.max_identifier_length;
+            let ascii_identifiers = options.ascii_identifiers;
+            DefaultMatcher::take_while(
+                move |buff, ch| {
+                    let first = buff.len() == ch.len_utf8();
+                    ch == '_'
+                        || match (ascii_identifiers, first) {
+                            (true, true) => ch.is_ascii_alphabetic(),
+                            (true, false) => ch.is_ascii_alphanumeric(),
+                            (false, true) => unicode_ident::is_xid_start(ch),
+                            (false, false) => unicode_ident::is_xid_continue(ch),
+                        }
+                },
+                1,
+                move |value, _| {
+                    let name = match is_nfc_quick(value.chars()) {
+                        IsNormalized::Yes => value.to_string(),
+                        _ => value.nfc().collect(),
+                    };
+                    match max_identifier_length {
+                        Some(max) if name.chars().count() > max => {
+                            Err(TokenizationError::IdentifierTooLong(name))
+                        }
+                        _ => Ok(token(name)),
                     }
-                    Ok(Token::RightBrace)
-                }),
-                DefaultMatcher::simple_text(",", Token::Comma),
-                DefaultMatcher::simple_text(".", Token::Dot),
-                DefaultMatcher::simple_text("=", Token::Equal),
-                DefaultMatcher::simple_text("==", Token::DoubleEqual),
-                DefaultMatcher::simple_text("!", Token::Bang),
-                DefaultMatcher::simple_text("!=", Token::BangEqual),
-                DefaultMatcher::simple_text(">", Token::Greater),
-                DefaultMatcher::simple_text(">=", Token::GreaterEqual),
-                DefaultMatcher::simple_text("<", Token::Less),
-                DefaultMatcher::simple_text("<=", Token::LessEqual),
-                DefaultMatcher::simple_text("&", Token::Ampersand),
-                DefaultMatcher::simple_text("&&", Token::DoubleAmpersand),
-                DefaultMatcher::simple_text("|", Token::Pipe),
-                DefaultMatcher::simple_text("||", Token::DoublePipe),
-                DefaultMatcher::text("\"", |_, state| {
-                    state.push(Self::CompositeString);
-                    Ok(Token::StringOpen)
-                }),
-                DefaultMatcher::filtered_collector(
-                    ["`"],
-                    |_, ch| ch == '#' || ch == '`',
-                    true,
-                    |pounds, _, state| {
-                        state.push(Self::RawString(pounds.len()));
+                },
+            )
+        }
+
+        let max_pounds = options.max_raw_string_pounds;
+        let keyword = |text: &str, token: Token| {
+            if options.case_insensitive_keywords {
+                DefaultMatcher::simple_text_ignore_case(text, token)
+            } else {
+                DefaultMatcher::simple_text(text, token)
+            }
+        };
+        match *self {
+            Self::Normal(from_string) => {
+                let mut matchers = vec![
+                    // symbols
+                    DefaultMatcher::simple_text("+", Token::Plus),
+                    DefaultMatcher::simple_text("-", Token::Minus),
+                    DefaultMatcher::simple_text("*", Token::Asterisk),
+                    DefaultMatcher::simple_text("/", Token::Slash),
+                    DefaultMatcher::simple_text("%", Token::Percent),
+                    DefaultMatcher::simple_text("(", Token::LeftParen),
+                    DefaultMatcher::simple_text(")", Token::RightParen),
+                    DefaultMatcher::simple_text("[", Token::LeftSquare),
+                    DefaultMatcher::simple_text("]", Token::RightSquare),
+                    DefaultMatcher::text("{", move |_, state| {
+                        if from_string {
+                            state.push(Self::Normal(true));
+                        }
+                        Ok(Token::LeftBrace)
+                    }),
+                    DefaultMatcher::text("}", move |_, state| {
+                        if from_string {
+                            state.pop()?;
+                        }
+                        Ok(Token::RightBrace)
+                    }),
+                    DefaultMatcher::simple_text(",", Token::Comma),
+                    DefaultMatcher::simple_text(".", Token::Dot),
+                    DefaultMatcher::simple_text("..", Token::DoubleDot),
+                    DefaultMatcher::simple_text("..=", Token::DoubleDotEqual),
+                    DefaultMatcher::simple_text("=", Token::Equal),
+                    DefaultMatcher::simple_text("==", Token::DoubleEqual),
+                    DefaultMatcher::simple_text("!", Token::Bang),
+                    DefaultMatcher::simple_text("!=", Token::BangEqual),
+                    DefaultMatcher::simple_text(">", Token::Greater),
+                    DefaultMatcher::simple_text(">=", Token::GreaterEqual),
+                    DefaultMatcher::simple_text("<", Token::Less),
+                    DefaultMatcher::simple_text("<=", Token::LessEqual),
+                    DefaultMatcher::simple_text("&", Token::Ampersand),
+                    DefaultMatcher::simple_text("&&", Token::DoubleAmpersand),
+                    DefaultMatcher::simple_text("|", Token::Pipe),
+                    DefaultMatcher::simple_text("||", Token::DoublePipe),
+                    DefaultMatcher::simple_text("+=", Token::PlusEqual),
+                    DefaultMatcher::simple_text("-=", Token::MinusEqual),
+                    DefaultMatcher::simple_text("*=", Token::AsteriskEqual),
+                    DefaultMatcher::simple_text("/=", Token::SlashEqual),
+                    DefaultMatcher::simple_text("%=", Token::PercentEqual),
+                    DefaultMatcher::text("\"", |_, state| {
+                        state.push(Self::CompositeString);
                         Ok(Token::StringOpen)
+                    }),
+                    DefaultMatcher::text("template\"", |_, state| {
+                        state.push(Self::CompositeString);
+                        Ok(Token::TemplateOpen)
+                    }),
+                    DefaultMatcher::filtered_collector(
+                        ["`"],
+                        move |buff, ch| (ch == '#' && buff.len() <= max_pounds) || ch == '`',
+                        true,
+                        |pounds, _, state| {
+                            state.push(Self::RawString(pounds.len()));
+                            Ok(Token::StringOpen)
+                        },
+                    ),
+                    DefaultMatcher::filtered_collector(
+                        ["\n"],
+                        |buff, ch| match buff.len() {
+                            1 | 2 => ch == '<',
+                            3 => ch == '~',
+                            4 => ch == '_' || ch.is_alphabetic(),
+                            // spaces and tabs can only trail the delimiter
+                            _ if buff[3..buff.len() - ch.len_utf8()].contains([' ', '\t']) => {
+                                matches!(ch, ' ' | '\t' | '\r' | '\n')
+                            }
+                            _ => {
+                                matches!(ch, '_' | ' ' | '\t' | '\r' | '\n') || ch.is_alphanumeric()
+                            }
+                        },
+                        true,
+                        |opener, _, state| {
+                            let delimiter = opener[3..].trim_end_matches([' ', '\t', '\r']);
+                            state.push(Self::Heredoc(delimiter.to_string()));
+                            Ok(Token::StringOpen)
+                        },
+                    ),
+                    // keywords
+                    keyword("let", Token::Let),
+                    keyword("var", Token::Var),
+                    keyword("const", Token::Const),
+                    keyword("if", Token::If),
+                    keyword("else", Token::Else),
+                    keyword("for", Token::For),
+                    keyword("while", Token::While),
+                    keyword("class", Token::Class),
+                    keyword("fn", Token::Fn),
+                    keyword("return", Token::Return),
+                    keyword("in", Token::In),
+                    keyword("not", Token::Not),
+                    // literals
+                    DefaultMatcher::simple_text("null", Token::Null),
+                    DefaultMatcher::simple_text("true", Token::BoolLiteral(true)),
+                    DefaultMatcher::simple_text("false", Token::BoolLiteral(false)),
+                    identifier(options, Token::Identifier),
+                    // r#let, to name things after keywords
+                    ChainMatcher::new(
+                        [
+                            DefaultMatcher::fixed_text("r#"),
+                            identifier(options, |name| name),
+                        ],
+                        |_, [_, name], _| Ok(Token::RawIdentifier(name)),
+                    ),
+                    float_literal(""), // before ints, so that 1e5 is a float
+                    int_literal(""),
+                    DefaultMatcher::text(&options.line_comment, |_, state| {
+                        state.push(Self::Comment(CommentKind::Line, "\n".to_string()));
+                        Ok(Token::LineCommentOpen)
+                    }),
+                    {
+                        let terminator = options.block_comment.1.clone();
+                        DefaultMatcher::text(&options.block_comment.0, move |_, state| {
+                            state.push(Self::Comment(CommentKind::Block, terminator.clone()));
+                            Ok(Token::BlockCommentOpen)
+                        })
                     },
-                ),
-                // keywords
-                DefaultMatcher::simple_text("let", Token::Let),
-                DefaultMatcher::simple_text("var", Token::Var),
-                DefaultMatcher::simple_text("const", Token::Const),
-                DefaultMatcher::simple_text("if", Token::If),
-                DefaultMatcher::simple_text("else", Token::Else),
-                DefaultMatcher::simple_text("for", Token::For),
-                DefaultMatcher::simple_text("while", Token::While),
-                DefaultMatcher::simple_text("class", Token::Class),
-                DefaultMatcher::simple_text("fn", Token::Fn),
-                DefaultMatcher::simple_text("return", Token::Return),
-                // literals
-                DefaultMatcher::simple_text("null", Token::Null),
-                DefaultMatcher::simple_text("true", Token::BoolLiteral(true)),
-                DefaultMatcher::simple_text("false", Token::BoolLiteral(false)),
-                DefaultMatcher::take_while(
-                    |buff, ch| {
-                        ch == '_'
-                            || if buff.len() == 1 {
-                                ch.is_alphabetic()
+                    DefaultMatcher::simple_text(";", Token::SemiColon),
+                    DefaultMatcher::simple_text("?", Token::QuestionMark),
+                    DefaultMatcher::simple_text("?.", Token::QuestionMarkDot),
+                    DefaultMatcher::simple_text("??", Token::DoubleQuestionMark),
+                    DefaultMatcher::simple_text(":", Token::Colon),
+                ];
+                if ![&options.line_comment, &options.block_comment.0]
+                    .iter()
+                    .any(|opener| opener.starts_with('#'))
+                {
+                    // pounds without a raw string after them, the span is made absolute by the lexer
+                    matchers.push(DefaultMatcher::take_while(
+                        move |buff, ch| ch == '#' && buff.len() <= max_pounds + 1,
+                        1,
+                        move |pounds, _| {
+                            Err(if pounds.len() > max_pounds {
+                                TokenizationError::RawStringPoundLimit(0..pounds.len())
                             } else {
-                                ch.is_alphanumeric()
-                            }
+                                TokenizationError::UnterminatedRawStringOpener(0..pounds.len())
+                            })
+                        },
+                    ));
+                }
+                // heredoc openers without a newline after the delimiter
+                matchers.push(DefaultMatcher::take_while(
+                    |buff, ch| match buff.len() {
+                        1 | 2 => ch == '<',
+                        3 => ch == '~',
+                        4 => ch == '_' || ch.is_alphabetic(),
+                        _ => ch == '_' || ch.is_alphanumeric(),
                     },
-                    1,
-                    |value, _| Ok(Token::Identifier(value.to_string())),
-                ),
-                int_literal(""),
-                int_literal("-"),
-                float_literal(""),
-                float_literal("-"),
-                DefaultMatcher::text("//", |_, state| {
-                    state.push(Self::Comment("\n".to_string()));
-                    Ok(Token::LineCommentOpen)
-                }),
-                DefaultMatcher::text("/*", |_, state| {
-                    state.push(Self::Comment("*/".to_string()));
-                    Ok(Token::BlockCommentOpen)
-                }),
-                DefaultMatcher::simple_text(";", Token::SemiColon),
-            ],
+                    3,
+                    |opener, _| {
+                        Err(TokenizationError::UnterminatedHeredocOpener(
+                            0..opener.len(),
+                        ))
+                    },
+                ));
+                if options.lossless {
+                    matchers.push(DefaultMatcher::take_while(
+                        |_, ch| ch.is_whitespace(),
+                        1,
+                        |value, _| Ok(Token::Whitespace(value.to_string())),
+                    ));
+                }
+                matchers.extend(
+                    options
+                        .extra_keywords
+                        .iter()
+                        .map(|k| keyword(k, Token::CustomKeyword(k.clone()))),
+                );
+                matchers
+            }
             Self::CompositeString => vec![
                 DefaultMatcher::take_while(
                     |buff, _| !["\"", "${", "\\"].iter().any(|t| buff.ends_with(t)), // unclosed string literals
@@ -170,10 +341,14 @@ impl LexerState {
                     |value, _| Ok(Token::StringLiteral(value.to_string())),
                 ),
                 DefaultMatcher::collector(["\"", "${", "\\"], false, |value, _, _| {
+                    if value.is_empty() {
+                        // a backslash at the end of the source, the escapes match it otherwise
+                        return Err(TokenizationError::NoEscape(0..1));
+                    }
                     Ok(Token::StringLiteral(value.to_string()))
                 }),
                 DefaultMatcher::text("\"", |_, state| {
-                    state.pop();
+                    state.pop()?;
                     Ok(Token::StringClose)
                 }),
                 DefaultMatcher::text("${", |_, state| {
@@ -188,10 +363,10 @@ impl LexerState {
                             |val, _| Ok(val.to_string()),
                         ),
                     ],
-                    |_, [prefix, escaped], _| {
+                    |buffer, [prefix, escaped], _| {
                         parsing::escape(&format!("{}{}", prefix, escaped))
                             .map(|ch| Token::StringLiteral(ch.to_string()))
-                            .map_err(TokenizationError::EscapeParse)
+                            .map_err(|err| TokenizationError::EscapeParse(err, 0..buffer.len()))
                     },
                 ),
                 ChainMatcher::new(
@@ -202,7 +377,24 @@ impl LexerState {
                             |val, _| Ok(val.to_string()),
                         ),
                     ],
-                    |_, _, _| Err(TokenizationError::NoEscape),
+                    |buffer, _, _| Err(TokenizationError::NoEscape(0..buffer.len())),
+                ),
+                ChainMatcher::new(
+                    [
+                        DefaultMatcher::fixed_text("\\x"),
+                        DefaultMatcher::conditions(
+                            vec![
+                                Box::new(|_: &str, ch: char| !ch.is_whitespace() && ch != '"'),
+                                Box::new(|_: &str, ch: char| !ch.is_whitespace() && ch != '"'),
+                            ],
+                            |val, _| Ok(val.to_string()),
+                        ),
+                    ],
+                    |buffer, [_, hex], _| {
+                        parsing::parse_hex_escape(&hex)
+                            .map(|ch| Token::StringLiteral(ch.to_string()))
+                            .map_err(|err| TokenizationError::EscapeParse(err, 0..buffer.len()))
+                    },
                 ),
                 ChainMatcher::new(
                     [
@@ -211,20 +403,20 @@ impl LexerState {
                             ["}"],
                             |_, ch| !ch.is_whitespace(),
                             true,
-                            |hex, _, _| {
-                                parsing::parse_unicode(hex)
-                                    .map(|c| c.to_string())
-                                    .map_err(TokenizationError::UnicodeParse)
-                            },
+                            |hex, _, _| Ok(hex.to_string()),
                         ),
                     ],
-                    |_, [_, unicode], _| Ok(Token::StringLiteral(unicode.clone())),
+                    |buffer, [_, hex], _| {
+                        parsing::parse_unicode(&hex)
+                            .map(|ch| Token::StringLiteral(ch.to_string()))
+                            .map_err(|err| TokenizationError::UnicodeParse(err, 0..buffer.len()))
+                    },
                 ),
             ],
             Self::RawString(pounds) => {
                 let pound_terminator = ['`'] // ` followed by # `pounds` times
                     .into_iter()
-                    .chain(std::iter::repeat('#').take(pounds))
+                    .chain(std::iter::repeat_n('#', pounds))
                     .collect::<String>();
                 vec![
                     DefaultMatcher::collector(
@@ -233,7 +425,7 @@ impl LexerState {
                         |value, _, _| Ok(Token::StringLiteral(value.to_string())),
                     ),
                     DefaultMatcher::text(pound_terminator.clone().as_ref(), |_, state| {
-                        state.pop();
+                        state.pop()?;
                         Ok(Token::StringClose)
                     }),
                     {
@@ -247,13 +439,93 @@ impl LexerState {
                     },
                 ]
             }
-            Self::Comment(ref terminator) => vec![
+            Self::Heredoc(ref delimiter) => {
+                fn last_line(buff: &str) -> &str {
+                    buff.rsplit('\n').next().unwrap()
+                }
+                fn is_terminator(line: &str, delimiter: &str) -> bool {
+                    line.trim_start_matches([' ', '\t']) == delimiter
+                }
+                vec![
+                    {
+                        let delimiter = delimiter.clone();
+                        DefaultMatcher::custom(
+                            MatcherState::Open,
+                            move |buff, _| {
+                                let line = buff
+                                    .strip_prefix("\r\n")
+                                    .or(buff.strip_prefix('\n'))
+                                    .unwrap_or(buff);
+                                let rest = line.trim_start_matches([' ', '\t']);
+                                if rest == delimiter {
+                                    MatcherState::Closeable
+                                } else if buff == "\r" || delimiter.starts_with(rest) {
+                                    MatcherState::Open
+                                } else {
+                                    MatcherState::Broken
+                                }
+                            },
+                            |buffer, state| {
+                                state.pop()?;
+                                Ok((Token::StringClose, buffer.len()))
+                            },
+                        )
+                    },
+                    {
+                        let delimiter = delimiter.clone();
+                        let mut terminated = false;
+                        DefaultMatcher::custom(
+                            MatcherState::Open,
+                            move |buff, ch| {
+                                if terminated && ch != '_' && !ch.is_alphanumeric() {
+                                    return MatcherState::Broken;
+                                }
+                                terminated = buff.contains('\n')
+                                    && is_terminator(last_line(buff), &delimiter);
+                                if terminated {
+                                    MatcherState::Closeable
+                                } else {
+                                    MatcherState::Open
+                                }
+                            },
+                            |buffer, _| {
+                                // the terminator line is left for the terminator matcher
+                                let end = buffer.rfind('\n').unwrap();
+                                let terminator = &buffer[end + 1..];
+                                let indent = &terminator[..terminator.len()
+                                    - terminator.trim_start_matches([' ', '\t']).len()];
+                                let body = &buffer[..end];
+                                let body = body.strip_suffix('\r').unwrap_or(body);
+                                let value = body
+                                    .split('\n')
+                                    .map(|line| {
+                                        line.strip_prefix(indent)
+                                            .unwrap_or_else(|| line.trim_start_matches([' ', '\t']))
+                                    })
+                                    .collect::<Vec<_>>()
+                                    .join("\n");
+                                Ok((Token::StringLiteral(value), end))
+                            },
+                        )
+                    },
+                    {
+                        // unclosed string literals
+                        let delimiter = delimiter.clone();
+                        DefaultMatcher::take_while(
+                            move |buff, _| !is_terminator(last_line(buff), &delimiter),
+                            0,
+                            |value, _| Ok(Token::StringLiteral(value.to_string())),
+                        )
+                    },
+                ]
+            }
+            Self::Comment(kind, ref terminator) => vec![
                 DefaultMatcher::collector([terminator.clone().as_ref()], false, |value, _, _| {
                     Ok(Token::CommentLiteral(value.to_string()))
                 }),
-                DefaultMatcher::text(terminator.clone().as_ref(), |_, state| {
-                    state.pop();
-                    Ok(Token::CommentClose)
+                DefaultMatcher::text(terminator.clone().as_ref(), move |_, state| {
+                    state.pop()?;
+                    Ok(Token::CommentClose(kind))
                 }),
                 {
                     // unclosed string literals
@@ -286,14 +558,41 @@ impl LexerStateManager {
         self.states.front().unwrap()
     }
 
+    pub(super) fn is_root(&self) -> bool {
+        self.states.len() == 1
+    }
+
+    pub(super) fn in_raw_string(&self) -> bool {
+        matches!(self.get(), LexerState::RawString(_))
+    }
+
     pub(super) fn push(&mut self, state: LexerState) {
         self.states.push_front(state);
     }
 
-    pub(super) fn pop(&mut self) {
+    pub(super) fn pop(&mut self) -> Result<(), TokenizationError> {
         if self.states.len() < 2 {
-            panic!()
+            return Err(TokenizationError::StateUnderflow);
         }
-        self.states.pop_front().unwrap();
+        self.states.pop_front();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use crate::lexer::TokenizationError;
+
+    use super::{LexerState, LexerStateManager};
+
+    #[rstest]
+    fn pop_underflow() {
+        let mut state = LexerStateManager::new();
+        state.push(LexerState::CompositeString);
+        assert_eq!(Ok(()), state.pop());
+        assert_eq!(Err(TokenizationError::StateUnderflow), state.pop());
+        assert_eq!(&LexerState::Normal(false), state.get()); // the root state is kept
     }
 }
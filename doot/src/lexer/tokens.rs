@@ -1,56 +1,357 @@
+use std::fmt::Display;
+
+use super::parsing::{self, NumberParseError};
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Token {
     // symbols
-    Plus,             // +
-    Minus,            // -
-    Asterisk,         // *
-    Slash,            // /
-    LeftParen,        // (
-    RightParen,       // )
-    LeftSquare,       // [
-    RightSquare,      // ]
-    LeftBrace,        // {
-    RightBrace,       // }
-    Comma,            // ,
-    Dot,              // .
-    Equal,            // =
-    DoubleEqual,      // ==
-    Bang,             // !
-    BangEqual,        // !=
-    Greater,          // >
-    GreaterEqual,     // >=
-    Less,             // <
-    LessEqual,        // <=
-    Ampersand,        // &
-    DoubleAmpersand,  // &&
-    Pipe,             // |
-    DoublePipe,       // ||
-    StringOpen,       // ", #`
-    StringClose,      // ", `#
-    DollarLeftBrace,  // ${
-    LineCommentOpen,  // //
-    BlockCommentOpen, // /*
-    CommentClose,     // newline, */
-    SemiColon,        // ;
+    Plus,                      // +
+    Minus,                     // -
+    Asterisk,                  // *
+    Slash,                     // /
+    Percent,                   // %
+    LeftParen,                 // (
+    RightParen,                // )
+    LeftSquare,                // [
+    RightSquare,               // ]
+    LeftBrace,                 // {
+    RightBrace,                // }
+    Comma,                     // ,
+    Dot,                       // .
+    DoubleDot,                 // ..
+    DoubleDotEqual,            // ..=
+    Equal,                     // =
+    DoubleEqual,               // ==
+    Bang,                      // !
+    BangEqual,                 // !=
+    Greater,                   // >
+    GreaterEqual,              // >=
+    Less,                      // <
+    LessEqual,                 // <=
+    Ampersand,                 // &
+    DoubleAmpersand,           // &&
+    Pipe,                      // |
+    DoublePipe,                // ||
+    PlusEqual,                 // +=
+    MinusEqual,                // -=
+    AsteriskEqual,             // *=
+    SlashEqual,                // /=
+    PercentEqual,              // %=
+    StringOpen,                // ", #`, <<~END
+    StringClose,               // ", `#, END
+    TemplateOpen,              // template"
+    DollarLeftBrace,           // ${
+    LineCommentOpen,           // //
+    BlockCommentOpen,          // /*
+    CommentClose(CommentKind), // newline, */
+    SemiColon,                 // ;
+    QuestionMark,              // ?
+    QuestionMarkDot,           // ?.
+    DoubleQuestionMark,        // ??
+    Colon,                     // :
 
     // keywords
-    Let,    // let
-    Var,    // var
-    Const,  // const
-    If,     // if
-    Else,   // else
-    For,    // for
-    While,  // while
-    Class,  // class
-    Fn,     // fn
-    Return, // return
+    Let,                   // let
+    Var,                   // var
+    Const,                 // const
+    If,                    // if
+    Else,                  // else
+    For,                   // for
+    While,                 // while
+    Class,                 // class
+    Fn,                    // fn
+    Return,                // return
+    In,                    // in
+    Not,                   // not
+    CustomKeyword(String), // configured with LexerBuilder::keyword
 
     // literals
     Null,                   // null
     BoolLiteral(bool),      // true, false
     Identifier(String),     // foo
-    IntLiteral(i64),        // 1234
+    RawIdentifier(String),  // r#let (the name)
+    IntLiteral(u64),        // 1234 (the magnitude, - is a separate token)
     FloatLiteral(f64),      // 1234.5678
     StringLiteral(String),  // "hello, world" (the content)
     CommentLiteral(String), // // hello (the content)
+    Unknown(char),          // any character no token starts with, if enabled in LexerBuilder
+
+    // merged tokens, if enabled in LexerBuilder
+    Comment { kind: CommentKind, text: String }, // // hello
+    String(String),                              // "hello, world" without interpolations
+
+    Whitespace(String), // if enabled in LexerBuilder
+    Eof,                // end of the source, the last token unless lexing fails
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommentKind {
+    Line,  // //
+    Block, // /*
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenCategory {
+    Keyword,
+    Operator,
+    Literal,
+    Identifier,
+    Delimiter,
+    Trivia, // comments, whitespace, end of the source
+    Unknown,
+}
+
+impl Token {
+    pub fn category(&self) -> TokenCategory {
+        match self {
+            Token::Plus
+            | Token::Minus
+            | Token::Asterisk
+            | Token::Slash
+            | Token::Percent
+            | Token::Dot
+            | Token::DoubleDot
+            | Token::DoubleDotEqual
+            | Token::Equal
+            | Token::DoubleEqual
+            | Token::Bang
+            | Token::BangEqual
+            | Token::Greater
+            | Token::GreaterEqual
+            | Token::Less
+            | Token::LessEqual
+            | Token::Ampersand
+            | Token::DoubleAmpersand
+            | Token::Pipe
+            | Token::DoublePipe
+            | Token::PlusEqual
+            | Token::MinusEqual
+            | Token::AsteriskEqual
+            | Token::SlashEqual
+            | Token::PercentEqual
+            | Token::QuestionMark
+            | Token::QuestionMarkDot
+            | Token::DoubleQuestionMark
+            | Token::Colon => TokenCategory::Operator,
+            Token::LeftParen
+            | Token::RightParen
+            | Token::LeftSquare
+            | Token::RightSquare
+            | Token::LeftBrace
+            | Token::RightBrace
+            | Token::Comma
+            | Token::SemiColon
+            | Token::StringOpen
+            | Token::StringClose
+            | Token::TemplateOpen
+            | Token::DollarLeftBrace => TokenCategory::Delimiter,
+            Token::LineCommentOpen
+            | Token::BlockCommentOpen
+            | Token::CommentClose(_)
+            | Token::CommentLiteral(_)
+            | Token::Comment { .. }
+            | Token::Whitespace(_)
+            | Token::Eof => TokenCategory::Trivia,
+            Token::Let
+            | Token::Var
+            | Token::Const
+            | Token::If
+            | Token::Else
+            | Token::For
+            | Token::While
+            | Token::Class
+            | Token::Fn
+            | Token::Return
+            | Token::In
+            | Token::Not
+            | Token::CustomKeyword(_) => TokenCategory::Keyword,
+            Token::Null
+            | Token::BoolLiteral(_)
+            | Token::IntLiteral(_)
+            | Token::FloatLiteral(_)
+            | Token::StringLiteral(_)
+            | Token::String(_) => TokenCategory::Literal,
+            Token::Identifier(_) | Token::RawIdentifier(_) => TokenCategory::Identifier,
+            Token::Unknown(_) => TokenCategory::Unknown,
+        }
+    }
+
+    // the value of an IntLiteral, negative if it follows a Minus the parser folds into it
+    pub fn int_value(&self, negative: bool) -> Option<Result<i64, NumberParseError>> {
+        match self {
+            Token::IntLiteral(magnitude) => Some(parsing::fold_sign(*magnitude, negative)),
+            _ => None,
+        }
+    }
+}
+
+// canonical source text, string and comment literals are their content
+impl Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Plus => "+",
+            Token::Minus => "-",
+            Token::Asterisk => "*",
+            Token::Slash => "/",
+            Token::Percent => "%",
+            Token::LeftParen => "(",
+            Token::RightParen => ")",
+            Token::LeftSquare => "[",
+            Token::RightSquare => "]",
+            Token::LeftBrace => "{",
+            Token::RightBrace => "}",
+            Token::Comma => ",",
+            Token::Dot => ".",
+            Token::DoubleDot => "..",
+            Token::DoubleDotEqual => "..=",
+            Token::Equal => "=",
+            Token::DoubleEqual => "==",
+            Token::Bang => "!",
+            Token::BangEqual => "!=",
+            Token::Greater => ">",
+            Token::GreaterEqual => ">=",
+            Token::Less => "<",
+            Token::LessEqual => "<=",
+            Token::Ampersand => "&",
+            Token::DoubleAmpersand => "&&",
+            Token::Pipe => "|",
+            Token::DoublePipe => "||",
+            Token::PlusEqual => "+=",
+            Token::MinusEqual => "-=",
+            Token::AsteriskEqual => "*=",
+            Token::SlashEqual => "/=",
+            Token::PercentEqual => "%=",
+            Token::StringOpen | Token::StringClose => "\"",
+            Token::TemplateOpen => "template\"",
+            Token::DollarLeftBrace => "${",
+            Token::LineCommentOpen => "//",
+            Token::BlockCommentOpen => "/*",
+            Token::CommentClose(CommentKind::Line) => "\n",
+            Token::CommentClose(CommentKind::Block) => "*/", // the default closer
+            Token::SemiColon => ";",
+            Token::QuestionMark => "?",
+            Token::QuestionMarkDot => "?.",
+            Token::DoubleQuestionMark => "??",
+            Token::Colon => ":",
+            Token::Let => "let",
+            Token::Var => "var",
+            Token::Const => "const",
+            Token::If => "if",
+            Token::Else => "else",
+            Token::For => "for",
+            Token::While => "while",
+            Token::Class => "class",
+            Token::Fn => "fn",
+            Token::Return => "return",
+            Token::In => "in",
+            Token::Not => "not",
+            Token::CustomKeyword(keyword) => keyword,
+            Token::Null => "null",
+            Token::BoolLiteral(value) => return value.fmt(f),
+            Token::Identifier(name) => name,
+            Token::RawIdentifier(name) => return write!(f, "r#{}", name),
+            Token::IntLiteral(value) => return value.fmt(f),
+            Token::FloatLiteral(value) => return write!(f, "{:?}", value), // keeps the point
+            Token::StringLiteral(content) | Token::CommentLiteral(content) => content,
+            Token::Whitespace(text) => text,
+            Token::Unknown(ch) => return ch.fmt(f),
+            Token::Comment {
+                kind: CommentKind::Line,
+                text,
+            } => return write!(f, "//{}", text),
+            Token::Comment {
+                kind: CommentKind::Block,
+                text,
+            } => return write!(f, "/*{}*/", text),
+            Token::String(text) => return write!(f, "\"{}\"", escape_string(text)),
+            Token::Eof => "",
+        }
+        .fmt(f)
+    }
+}
+
+fn escape_string(text: &str) -> String {
+    text.chars()
+        .map(|ch| match ch {
+            '\n' => r"\n".to_string(),
+            '\r' => r"\r".to_string(),
+            '\t' => r"\t".to_string(),
+            '\\' => r"\\".to_string(),
+            '\0' => r"\0".to_string(),
+            '$' => r"\$".to_string(),
+            '"' => r#"\""#.to_string(),
+            ch => ch.to_string(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use crate::lexer::Lexer;
+
+    use super::{CommentKind, NumberParseError, Token, TokenCategory};
+
+    #[rstest]
+    #[case(Token::BoolLiteral(true), "true")]
+    #[case(Token::Identifier("foo".to_string()), "foo")]
+    #[case(Token::IntLiteral(5), "5")]
+    #[case(Token::FloatLiteral(1.0), "1.0")]
+    #[case(Token::FloatLiteral(1.5e20), "1.5e20")]
+    #[case(Token::StringLiteral("a b".to_string()), "a b")]
+    #[case(Token::CustomKeyword("match".to_string()), "match")]
+    #[case(Token::Unknown('@'), "@")]
+    #[case(Token::Eof, "")]
+    #[case(Token::CommentClose(CommentKind::Line), "\n")]
+    #[case(Token::CommentClose(CommentKind::Block), "*/")]
+    #[case(Token::Comment { kind: CommentKind::Line, text: " foo".to_string() }, "// foo")]
+    #[case(Token::Comment { kind: CommentKind::Block, text: " foo ".to_string() }, "/* foo */")]
+    #[case(Token::String("a\n\"${b}\"".to_string()), r#""a\n\"\${b}\"""#)]
+    fn display(#[case] token: Token, #[case] expected: &str) {
+        assert_eq!(expected, token.to_string());
+    }
+
+    #[rstest]
+    #[case(Token::FloatLiteral(1.0))]
+    #[case(Token::FloatLiteral(1.5e20))]
+    #[case(Token::IntLiteral(1234))]
+    #[case(Token::RawIdentifier("let".to_string()))]
+    fn display_relexes(#[case] token: Token) {
+        let tokens: Vec<_> = Lexer::new(token.to_string().chars()).collect();
+        assert_eq!(vec![Ok(token), Ok(Token::Eof)], tokens);
+    }
+
+    #[rstest]
+    #[case(Token::Plus, TokenCategory::Operator)]
+    #[case(Token::Colon, TokenCategory::Operator)]
+    #[case(Token::LeftBrace, TokenCategory::Delimiter)]
+    #[case(Token::StringOpen, TokenCategory::Delimiter)]
+    #[case(Token::CommentLiteral(" foo".to_string()), TokenCategory::Trivia)]
+    #[case(Token::Not, TokenCategory::Keyword)]
+    #[case(Token::CustomKeyword("match".to_string()), TokenCategory::Keyword)]
+    #[case(Token::Null, TokenCategory::Literal)]
+    #[case(Token::StringLiteral("a".to_string()), TokenCategory::Literal)]
+    #[case(Token::Identifier("foo".to_string()), TokenCategory::Identifier)]
+    #[case(Token::Unknown('@'), TokenCategory::Unknown)]
+    fn category(#[case] token: Token, #[case] expected: TokenCategory) {
+        assert_eq!(expected, token.category());
+    }
+
+    #[rstest]
+    #[case(Token::IntLiteral(9223372036854775808), true, Some(Ok(i64::MIN)))]
+    #[case(
+        Token::IntLiteral(9223372036854775808),
+        false,
+        Some(Err(NumberParseError::PositiveOverflow))
+    )]
+    #[case(Token::IntLiteral(1), true, Some(Ok(-1)))]
+    #[case(Token::FloatLiteral(1.0), false, None)]
+    fn int_values(
+        #[case] token: Token,
+        #[case] negative: bool,
+        #[case] expected: Option<Result<i64, NumberParseError>>,
+    ) {
+        assert_eq!(expected, token.int_value(negative));
+    }
 }
@@ -0,0 +1,368 @@
+use std::collections::HashMap;
+
+use crate::{
+    directives::DirectiveTable,
+    edition::Edition,
+    edits::TextEdit,
+    lexer::{incremental::LexedSource, tokens::Token},
+    source_map::SourceFile,
+    span::Span,
+};
+
+pub const LINT_NAMES: &[&str] = &[
+    "empty_interpolation",
+    "raw_string_interpolation",
+    "unterminated_interpolation",
+    "reserved_word",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LintLevel {
+    Allow, // not reported
+    Warn,
+    Deny, // reported as an error
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lint {
+    name: &'static str, // one of LINT_NAMES
+    message: String,
+    span: Span,
+    fix: Option<TextEdit>,
+    level: LintLevel,
+}
+
+impl Lint {
+    fn new(name: &'static str, message: &str, span: Span, fix: Option<TextEdit>) -> Self {
+        Self {
+            name,
+            message: message.to_string(),
+            span,
+            fix,
+            level: LintLevel::Warn,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn level(&self) -> LintLevel {
+        self.level
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn span(&self) -> &Span {
+        &self.span
+    }
+
+    pub fn fix(&self) -> Option<&TextEdit> {
+        self.fix.as_ref()
+    }
+}
+
+// levels for a run, lints not configured are warnings
+#[derive(Debug, Clone, Default)]
+pub struct LintConfig {
+    levels: HashMap<String, LintLevel>,
+}
+
+impl LintConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // names can also be written with - instead of _
+    pub fn level(mut self, name: &str, level: LintLevel) -> Self {
+        self.levels.insert(name.replace('-', "_"), level);
+        self
+    }
+
+    pub fn level_of(&self, name: &str) -> LintLevel {
+        self.levels.get(name).copied().unwrap_or(LintLevel::Warn)
+    }
+
+    // configured names that are not in LINT_NAMES
+    pub fn unknown_names(&self) -> Vec<&str> {
+        let mut names: Vec<_> = self
+            .levels
+            .keys()
+            .map(String::as_str)
+            .filter(|name| !LINT_NAMES.contains(name))
+            .collect();
+        names.sort();
+        names
+    }
+}
+
+// every lint, sorted by position and without the allowed or ignored ones
+pub fn lint(lexed: &LexedSource, edition: Edition, config: &LintConfig) -> Vec<Lint> {
+    let file = SourceFile::new("", lexed.source());
+    let directives = DirectiveTable::from_tokens(lexed.tokens());
+    let mut lints = interpolation_lints(lexed);
+    lints.extend(reserved_word_lints(lexed, edition));
+    lints.sort_by_key(|lint| lint.span.start());
+    lints
+        .into_iter()
+        .map(|lint| Lint {
+            level: config.level_of(lint.name),
+            ..lint
+        })
+        .filter(|lint| lint.level != LintLevel::Allow)
+        .filter(|lint| !directives.allowed(lint.name, lint.span.start()))
+        .filter(|lint| !directives.ignored(lint.name, lint.span.start(), &file))
+        .collect()
+}
+
+pub fn interpolation_lints(lexed: &LexedSource) -> Vec<Lint> {
+    let source = lexed.source();
+    let tokens = lexed.tokens();
+    let mut lints = vec![];
+    let mut braces = vec![]; // indexes of the open { and ${ tokens
+    let mut string = None; // index of the opener of the innermost string
+    for (i, lexed_token) in tokens.iter().enumerate() {
+        let span = lexed_token.span();
+        match lexed_token.token() {
+            Token::DollarLeftBrace | Token::LeftBrace => braces.push(i),
+            Token::RightBrace => {
+                let open = braces.pop();
+                if i > 0 && open == Some(i - 1) && tokens[i - 1].token() == &Token::DollarLeftBrace
+                {
+                    let span = tokens[i - 1].span().join(&span);
+                    lints.push(Lint::new(
+                        "empty_interpolation",
+                        "empty interpolation",
+                        span,
+                        Some(TextEdit::delete(span.range())),
+                    ));
+                }
+            }
+            Token::StringOpen => string = Some(i),
+            Token::StringLiteral(_) if lexed_token.in_raw_string() => {
+                let Some(opener) = string.map(|i| tokens[i].span()) else {
+                    continue;
+                };
+                for (offset, _) in source[span.range()].match_indices("${") {
+                    let start = span.start() + offset;
+                    let fix = tokens
+                        .get(i + 1)
+                        .filter(|t| t.token() == &Token::StringClose)
+                        .map(|close| {
+                            TextEdit::new(
+                                opener.join(&close.span()).range(),
+                                &format!("\"{}\"", escape_quoted(&source[span.range()])),
+                            )
+                        });
+                    lints.push(Lint::new(
+                        "raw_string_interpolation",
+                        "raw strings are never interpolated",
+                        Span::new(span.source_id(), start..start + 2),
+                        fix,
+                    ));
+                }
+            }
+            Token::StringClose => string = None,
+            _ => {}
+        }
+    }
+    for i in braces {
+        if tokens[i].token() == &Token::DollarLeftBrace {
+            let span = tokens[i].span();
+            lints.push(Lint::new(
+                "unterminated_interpolation",
+                "interpolation is never closed",
+                span,
+                Some(TextEdit::new(span.range(), r"\${")),
+            ));
+        }
+    }
+    lints.sort_by_key(|lint| lint.span.start());
+    lints
+}
+
+pub fn reserved_word_lints(lexed: &LexedSource, edition: Edition) -> Vec<Lint> {
+    lexed
+        .tokens()
+        .iter()
+        .filter_map(|t| match t.token() {
+            Token::Identifier(name) if edition.reserved_words().contains(&name.as_str()) => {
+                Some(Lint::new(
+                    "reserved_word",
+                    &format!("{} is reserved for future use", name),
+                    t.span(),
+                    None,
+                ))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+// content of a raw string as a quoted string body, keeping ${ as an interpolation
+fn escape_quoted(content: &str) -> String {
+    content.replace('\\', r"\\").replace('"', r#"\""#)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ops::Range, slice};
+
+    use rstest::rstest;
+
+    use crate::{
+        edits::{TextEdit, apply_edits},
+        lexer::{builder::LexerBuilder, incremental::LexedSource},
+    };
+
+    use super::{Edition, LintConfig, LintLevel, interpolation_lints, lint, reserved_word_lints};
+
+    fn lints(source: &str) -> Vec<(&'static str, Range<usize>, Option<String>)> {
+        let lexed = LexedSource::new(source, LexerBuilder::new());
+        interpolation_lints(&lexed)
+            .into_iter()
+            .map(|lint| {
+                let fixed = lint
+                    .fix()
+                    .map(|fix| apply_edits(source, slice::from_ref(fix)).unwrap());
+                (lint.name, lint.span().range(), fixed)
+            })
+            .collect()
+    }
+
+    #[rstest]
+    #[case(r#""a${b}c" { }"#)]
+    #[case(r#""${ {} }""#)]
+    #[case("#`a $ {b}`#")]
+    #[case("<<~END\n  ${a}\n  END")] // heredocs are documented as not interpolated
+    #[case("}")] // closes nothing
+    #[case("} {}")]
+    fn no_lints(#[case] source: &str) {
+        assert!(lints(source).is_empty());
+    }
+
+    #[rstest]
+    #[case(
+        r#""a${}b""#,
+        vec![("empty_interpolation", 2..5, Some(r#""ab""#.to_string()))]
+    )]
+    #[case(
+        "#`a ${b}`#",
+        vec![("raw_string_interpolation", 4..6, Some(r#""a ${b}""#.to_string()))]
+    )]
+    #[case(
+        "`a ${b}`",
+        vec![("raw_string_interpolation", 3..5, Some(r#""a ${b}""#.to_string()))]
+    )]
+    #[case(
+        r#"#`"\${b}`#"#,
+        vec![("raw_string_interpolation", 4..6, Some(r#""\"\\${b}""#.to_string()))]
+    )]
+    #[case("#`${a}", vec![("raw_string_interpolation", 2..4, None)])] // unclosed
+    #[case(
+        r#""cost: ${""#,
+        vec![("unterminated_interpolation", 7..9, Some(r#""cost: \${""#.to_string()))]
+    )]
+    #[case(
+        r#""fo${{}o""#,
+        vec![("unterminated_interpolation", 3..5, Some(r#""fo\${{}o""#.to_string()))]
+    )]
+    fn found(
+        #[case] source: &str,
+        #[case] expected: Vec<(&'static str, Range<usize>, Option<String>)>,
+    ) {
+        assert_eq!(expected, lints(source));
+    }
+
+    #[rstest]
+    fn fix_is_a_text_edit() {
+        let lexed = LexedSource::new(r#""${}""#, LexerBuilder::new());
+        assert_eq!(
+            Some(&TextEdit::delete(1..4)),
+            interpolation_lints(&lexed)[0].fix()
+        );
+    }
+
+    #[rstest]
+    fn reserved_words() {
+        let lexed = LexedSource::new(
+            "let enum = match; // match\n\"loop\" matches r#type",
+            LexerBuilder::new(),
+        );
+        let lints: Vec<_> = reserved_word_lints(&lexed, Edition::Edition2025)
+            .into_iter()
+            .map(|lint| (lint.message().to_string(), lint.span().range()))
+            .collect();
+        assert_eq!(
+            vec![
+                ("enum is reserved for future use".to_string(), 4..8),
+                ("match is reserved for future use".to_string(), 11..16),
+            ],
+            lints
+        );
+    }
+
+    #[rstest]
+    fn levels() {
+        let lexed = LexedSource::new("let enum = \"${}\"; type", LexerBuilder::new());
+        let config = LintConfig::new()
+            .level("empty-interpolation", LintLevel::Deny)
+            .level("reserved_word", LintLevel::Allow)
+            .level("unused_variable", LintLevel::Warn);
+        let lints: Vec<_> = lint(&lexed, Edition::Edition2025, &config)
+            .into_iter()
+            .map(|lint| (lint.name().to_string(), lint.level()))
+            .collect();
+        assert_eq!(
+            vec![("empty_interpolation".to_string(), LintLevel::Deny)],
+            lints
+        );
+        assert_eq!(vec!["unused_variable"], config.unknown_names());
+        let all = lint(&lexed, Edition::Edition2025, &LintConfig::new());
+        assert_eq!(3, all.len());
+        assert!(all.iter().all(|lint| lint.level() == LintLevel::Warn));
+    }
+
+    #[rstest]
+    fn allow_directives() {
+        let source = "let enum = \"${}\";\n// doot-allow(reserved-word)\nlet type = \"${}\";";
+        let lexed = LexedSource::new(source, LexerBuilder::new());
+        let lints: Vec<_> = lint(&lexed, Edition::Edition2025, &LintConfig::new())
+            .into_iter()
+            .map(|lint| (lint.name(), lint.span().start()))
+            .collect();
+        assert_eq!(
+            vec![
+                ("reserved_word", 4),
+                ("empty_interpolation", 12),
+                ("empty_interpolation", 59),
+            ],
+            lints
+        );
+    }
+
+    #[rstest]
+    #[case(false)]
+    #[case(true)]
+    fn ignore_comments(#[case] merge_comments: bool) {
+        let source = "let enum = 1;\n\
+            // doot-ignore: reserved-word, empty_interpolation\n\
+            let type = \"${}\";\n\
+            let loop = 2; // doot-ignore: reserved_word\n\
+            let match = 3;";
+        let lexed = LexedSource::new(source, LexerBuilder::new().merge_comments(merge_comments));
+        let lints: Vec<_> = lint(&lexed, Edition::Edition2025, &LintConfig::new())
+            .into_iter()
+            .map(|lint| lint.message().to_string())
+            .collect();
+        assert_eq!(
+            vec![
+                "enum is reserved for future use",
+                "loop is reserved for future use", // a comment after the code ignores the next line
+            ],
+            lints
+        );
+    }
+}
@@ -0,0 +1,246 @@
+use crate::{
+    Source,
+    span::{LspPosition, LspRange, SourceId, SourcePosition, Span},
+};
+
+// a source with its line starts, converting between byte offsets and positions.
+// lines end with \n or \r\n
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceFile {
+    name: String,
+    text: String,
+    line_starts: Vec<usize>,
+}
+
+impl SourceFile {
+    pub fn new(name: &str, text: &str) -> Self {
+        let line_starts = [0]
+            .into_iter()
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self {
+            name: name.to_string(),
+            text: text.to_string(),
+            line_starts,
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    // 1-based, without the line ending
+    pub fn line_text(&self, line: u32) -> Option<&str> {
+        let index = (line as usize).checked_sub(1)?;
+        let start = *self.line_starts.get(index)?;
+        let end = self
+            .line_starts
+            .get(index + 1)
+            .map_or(self.text.len(), |next| next - 1);
+        let text = &self.text[start..end];
+        Some(text.strip_suffix('\r').unwrap_or(text))
+    }
+
+    // of the character at offset, offsets past the end are clamped to the end
+    pub fn position(&self, offset: usize) -> SourcePosition {
+        let (index, before) = self.locate(offset);
+        SourcePosition::new(index as u32 + 1, before.chars().count() as u32 + 1)
+    }
+
+    // zero based line and utf-16 character, as in the language server protocol
+    pub fn lsp_position(&self, offset: usize) -> LspPosition {
+        let (index, before) = self.locate(offset);
+        let character: usize = before.chars().map(char::len_utf16).sum();
+        LspPosition {
+            line: index as u32,
+            character: character as u32,
+        }
+    }
+
+    pub fn lsp_range(&self, span: &Span) -> LspRange {
+        LspRange {
+            start: self.lsp_position(span.start()),
+            end: self.lsp_position(span.end()),
+        }
+    }
+
+    // the column can be one past the last character of the line, the end of the line
+    pub fn offset(&self, position: SourcePosition) -> Option<usize> {
+        let line_start = *self
+            .line_starts
+            .get((position.line() as usize).checked_sub(1)?)?;
+        let line = self.line_text(position.line())?;
+        let col = (position.col() as usize).checked_sub(1)?;
+        line.char_indices()
+            .map(|(i, _)| i)
+            .chain([line.len()])
+            .nth(col)
+            .map(|i| line_start + i)
+    }
+
+    // line index and the text of the line before offset. offsets are clamped to the text and
+    // rounded down to a char boundary, the \n of \r\n is at the same position as the \r
+    fn locate(&self, offset: usize) -> (usize, &str) {
+        let mut offset = offset.min(self.text.len());
+        while !self.text.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        let index = self.line_starts.partition_point(|start| *start <= offset) - 1;
+        let before = &self.text[self.line_starts[index]..offset];
+        if self.text[offset..].starts_with('\n') {
+            (index, before.strip_suffix('\r').unwrap_or(before))
+        } else {
+            (index, before)
+        }
+    }
+}
+
+impl Source for SourceFile {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn chars(&self) -> impl Iterator<Item = char> + Send {
+        self.text.chars()
+    }
+}
+
+// every source of a run, the SourceId of a file is its index
+#[derive(Debug, Clone, Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // pass the id to LexerBuilder::source_id so that spans point into the file
+    pub fn add(&mut self, name: &str, text: &str) -> SourceId {
+        self.files.push(SourceFile::new(name, text));
+        SourceId(self.files.len() as u32 - 1)
+    }
+
+    pub fn get(&self, id: SourceId) -> Option<&SourceFile> {
+        self.files.get(id.0 as usize)
+    }
+
+    pub fn file_of(&self, span: &Span) -> Option<&SourceFile> {
+        self.get(span.source_id())
+    }
+
+    pub fn files(&self) -> impl Iterator<Item = (SourceId, &SourceFile)> {
+        self.files
+            .iter()
+            .enumerate()
+            .map(|(i, file)| (SourceId(i as u32), file))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use crate::{
+        Source,
+        lexer::builder::LexerBuilder,
+        span::{LspPosition, SourceId, SourcePosition},
+    };
+
+    use super::{SourceFile, SourceMap};
+
+    #[rstest]
+    #[case("let a", 4, (1, 5))]
+    #[case("a\nbc", 3, (2, 2))]
+    #[case("a\r\nbc", 4, (2, 2))]
+    #[case("a\r\nbc", 1, (1, 2))] // the line ending belongs to its line
+    #[case("a\r\nbc", 2, (1, 2))] // \r\n is a single line ending
+    #[case("a\n", 2, (2, 1))]
+    #[case("é😀x", 6, (1, 3))] // in characters
+    #[case("é", 1, (1, 1))] // inside a character
+    #[case("a", 10, (1, 2))]
+    fn positions(#[case] text: &str, #[case] offset: usize, #[case] expected: (u32, u32)) {
+        let file = SourceFile::new("a.dt", text);
+        let position = SourcePosition::new(expected.0, expected.1);
+        assert_eq!(position, file.position(offset));
+    }
+
+    #[rstest]
+    #[case("a\r\nbc", (2, 1), Some(3))]
+    #[case("a\r\nbc", (2, 3), Some(5))] // end of the line
+    #[case("a\r\nbc", (1, 2), Some(1))]
+    #[case("a\r\nbc", (1, 3), None)]
+    #[case("é😀x", (1, 3), Some(6))]
+    #[case("a", (2, 1), None)]
+    #[case("a", (0, 1), None)]
+    #[case("a", (1, 0), None)]
+    fn offsets(#[case] text: &str, #[case] position: (u32, u32), #[case] expected: Option<usize>) {
+        let file = SourceFile::new("a.dt", text);
+        assert_eq!(
+            expected,
+            file.offset(SourcePosition::new(position.0, position.1))
+        );
+    }
+
+    #[rstest]
+    fn offsets_roundtrip() {
+        let file = SourceFile::new("a.dt", "let é = 1;\r\n\n  \"😀\"\n");
+        for (offset, _) in file.text().char_indices() {
+            let expected = if file.text()[..offset].ends_with('\r') {
+                offset - 1 // the \n of \r\n
+            } else {
+                offset
+            };
+            assert_eq!(Some(expected), file.offset(file.position(offset)));
+        }
+    }
+
+    #[rstest]
+    #[case("a\r\nbc\n", vec![Some("a"), Some("bc"), Some(""), None])]
+    #[case("", vec![Some(""), None])]
+    fn lines(#[case] text: &str, #[case] expected: Vec<Option<&str>>) {
+        let file = SourceFile::new("a.dt", text);
+        assert_eq!(expected.len() - 1, file.line_count());
+        let lines: Vec<_> = (1..=expected.len() as u32)
+            .map(|line| file.line_text(line))
+            .collect();
+        assert_eq!(expected, lines);
+        assert_eq!(None, file.line_text(0));
+    }
+
+    #[rstest]
+    fn lsp_positions() {
+        let file = SourceFile::new("a.dt", "a\r\né😀x");
+        assert_eq!(
+            LspPosition {
+                line: 1,
+                character: 3
+            },
+            file.lsp_position(9)
+        );
+    }
+
+    #[rstest]
+    fn map() {
+        let mut map = SourceMap::new();
+        let a = map.add("a.dt", "let a");
+        let b = map.add("b.dt", "x\ny");
+        assert_eq!((SourceId(0), SourceId(1)), (a, b));
+        let file = map.get(b).unwrap();
+        let mut lexer = LexerBuilder::new().source_id(b).build(file.chars());
+        lexer.nth(1);
+        let span = lexer.span();
+        assert_eq!("b.dt", map.file_of(&span).unwrap().name());
+        assert_eq!(SourcePosition::new(2, 1), file.position(span.start()));
+        assert_eq!(
+            vec!["a.dt", "b.dt"],
+            map.files().map(|(_, file)| file.name()).collect::<Vec<_>>()
+        );
+        assert!(map.get(SourceId(2)).is_none());
+    }
+}
@@ -0,0 +1,216 @@
+use std::{fmt::Display, ops::Range};
+
+use crate::source_map::SourceFile;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SourcePosition {
+    line: u32,
+    col: u32,
+}
+
+impl SourcePosition {
+    pub fn new(line: u32, col: u32) -> Self {
+        Self { line, col }
+    }
+
+    pub fn line(&self) -> u32 {
+        self.line
+    }
+
+    pub fn col(&self) -> u32 {
+        self.col
+    }
+
+    // of the character at a byte offset of text, see SourceFile::position for many lookups
+    pub fn from_offset(text: &str, offset: usize) -> Self {
+        SourceFile::new("", text).position(offset)
+    }
+}
+
+impl Display for SourcePosition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.col)
+    }
+}
+
+#[derive(Debug)]
+pub struct SourceElement<T> {
+    value: T,
+    start: SourcePosition,
+    stop: SourcePosition,
+}
+
+impl<T> SourceElement<T> {
+    pub fn new(value: T, start: SourcePosition, stop: SourcePosition) -> Self {
+        Self { value, start, stop }
+    }
+
+    pub fn value(&self) -> &T {
+        &self.value
+    }
+
+    pub fn start(&self) -> &SourcePosition {
+        &self.start
+    }
+
+    pub fn stop(&self) -> &SourcePosition {
+        &self.stop
+    }
+}
+
+// identifies the source a span points into, chosen by whoever owns the sources
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SourceId(pub u32);
+
+// byte range in a source
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Span {
+    start: usize,
+    end: usize,
+    source_id: SourceId,
+}
+
+impl Span {
+    pub fn new(source_id: SourceId, range: Range<usize>) -> Self {
+        Self {
+            start: range.start,
+            end: range.end.max(range.start),
+            source_id,
+        }
+    }
+
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    pub fn source_id(&self) -> SourceId {
+        self.source_id
+    }
+
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    // smallest span covering both, in the source of self
+    pub fn join(&self, other: &Span) -> Span {
+        Span::new(
+            self.source_id,
+            self.start.min(other.start)..self.end.max(other.end),
+        )
+    }
+
+    pub fn contains(&self, offset: usize) -> bool {
+        self.range().contains(&offset)
+    }
+
+    // empty spans intersect the spans they are in
+    pub fn intersects(&self, other: &Span) -> bool {
+        let inside = |empty: &Span, span: &Span| {
+            empty.is_empty() && (span.contains(empty.start) || span.start == empty.start)
+        };
+        self.source_id == other.source_id
+            && ((self.start < other.end && other.start < self.end)
+                || inside(self, other)
+                || inside(other, self))
+    }
+
+    // text is the source the span points into, see SourceFile::lsp_range for many lookups
+    pub fn to_lsp(&self, text: &str) -> LspRange {
+        SourceFile::new("", text).lsp_range(self)
+    }
+}
+
+// zero based line and utf-16 character, as in the language server protocol
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct LspPosition {
+    pub line: u32,
+    pub character: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LspRange {
+    pub start: LspPosition,
+    pub end: LspPosition,
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::{LspPosition, LspRange, SourceId, SourcePosition, Span};
+
+    fn span(range: std::ops::Range<usize>) -> Span {
+        Span::new(SourceId(0), range)
+    }
+
+    #[rstest]
+    #[case(span(0..2), span(5..7), span(0..7))]
+    #[case(span(3..9), span(4..5), span(3..9))]
+    fn join(#[case] a: Span, #[case] b: Span, #[case] expected: Span) {
+        assert_eq!(expected, a.join(&b));
+        assert_eq!(expected, b.join(&a));
+    }
+
+    #[rstest]
+    #[case(span(0..2), span(1..3), true)]
+    #[case(span(0..2), span(2..3), false)] // touching
+    #[case(span(2..2), span(0..4), true)]
+    #[case(span(2..2), span(2..2), true)]
+    #[case(span(0..2), Span::new(SourceId(1), 0..2), false)]
+    fn intersects(#[case] a: Span, #[case] b: Span, #[case] expected: bool) {
+        assert_eq!(expected, a.intersects(&b));
+        assert_eq!(expected, b.intersects(&a));
+    }
+
+    #[rstest]
+    fn contains() {
+        assert!(span(1..3).contains(1));
+        assert!(!span(1..3).contains(3));
+    }
+
+    #[rstest]
+    #[case("let a", 4, SourcePosition::new(1, 5))]
+    #[case("a\nbc", 3, SourcePosition::new(2, 2))]
+    #[case("é😀x", 6, SourcePosition::new(1, 3))] // in characters
+    #[case("a", 10, SourcePosition::new(1, 2))]
+    fn position_from_offset(
+        #[case] text: &str,
+        #[case] offset: usize,
+        #[case] expected: SourcePosition,
+    ) {
+        assert_eq!(expected, SourcePosition::from_offset(text, offset));
+    }
+
+    #[rstest]
+    #[case("let a", span(4..5), (0, 4), (0, 5))]
+    #[case("a\nbc", span(2..4), (1, 0), (1, 2))]
+    #[case("é😀x", span(6..7), (0, 3), (0, 4))] // utf-16 characters
+    #[case("a", span(0..10), (0, 0), (0, 1))]
+    fn to_lsp(
+        #[case] text: &str,
+        #[case] span: Span,
+        #[case] start: (u32, u32),
+        #[case] end: (u32, u32),
+    ) {
+        let position = |(line, character)| LspPosition { line, character };
+        assert_eq!(
+            LspRange {
+                start: position(start),
+                end: position(end),
+            },
+            span.to_lsp(text)
+        );
+    }
+}
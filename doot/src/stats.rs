@@ -0,0 +1,152 @@
+use std::collections::BTreeMap;
+
+use crate::lexer::tokens::{Token, TokenCategory};
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct TokenStats {
+    kinds: BTreeMap<String, usize>,
+    identifiers: BTreeMap<String, usize>,
+    statements: usize,
+    statement_tokens: usize, // trivia excluded
+    max_nesting: usize,
+}
+
+impl TokenStats {
+    pub fn from_tokens<'a>(tokens: impl IntoIterator<Item = &'a Token>) -> Self {
+        let mut stats = Self::default();
+        let mut nesting: usize = 0;
+        let mut current_statement = 0;
+        for token in tokens {
+            *stats.kinds.entry(kind_name(token)).or_default() += 1;
+            match token {
+                Token::Identifier(name) | Token::RawIdentifier(name) => {
+                    *stats.identifiers.entry(name.clone()).or_default() += 1
+                }
+                Token::LeftParen
+                | Token::LeftSquare
+                | Token::LeftBrace
+                | Token::DollarLeftBrace => {
+                    nesting += 1;
+                    stats.max_nesting = stats.max_nesting.max(nesting);
+                }
+                Token::RightParen | Token::RightSquare | Token::RightBrace => {
+                    nesting = nesting.saturating_sub(1)
+                }
+                _ => {}
+            }
+            if token.category() == TokenCategory::Trivia {
+                continue;
+            }
+            current_statement += 1;
+            if *token == Token::SemiColon {
+                stats.statements += 1;
+                stats.statement_tokens += current_statement;
+                current_statement = 0;
+            }
+        }
+        if current_statement > 0 {
+            stats.statements += 1; // without a trailing ;
+            stats.statement_tokens += current_statement;
+        }
+        stats
+    }
+
+    pub fn kinds(&self) -> &BTreeMap<String, usize> {
+        &self.kinds
+    }
+
+    pub fn identifiers(&self) -> &BTreeMap<String, usize> {
+        &self.identifiers
+    }
+
+    pub fn statements(&self) -> usize {
+        self.statements
+    }
+
+    // in tokens
+    pub fn average_statement_length(&self) -> f64 {
+        if self.statements == 0 {
+            0.0
+        } else {
+            self.statement_tokens as f64 / self.statements as f64
+        }
+    }
+
+    pub fn max_nesting(&self) -> usize {
+        self.max_nesting
+    }
+}
+
+fn kind_name(token: &Token) -> String {
+    let debug = format!("{:?}", token);
+    debug
+        .split(['(', ' '])
+        .next()
+        .unwrap_or_default()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use crate::lexer::{Lexer, tokens::Token};
+
+    use super::TokenStats;
+
+    fn stats(source: &str) -> TokenStats {
+        let tokens: Vec<Token> = Lexer::new(source.chars()).map(Result::unwrap).collect();
+        TokenStats::from_tokens(&tokens)
+    }
+
+    #[rstest]
+    fn counts() {
+        let stats = stats("let a = b; // note\nlet c = a;");
+        assert_eq!(
+            vec![
+                ("CommentClose", 1),
+                ("CommentLiteral", 1),
+                ("Eof", 1),
+                ("Equal", 2),
+                ("Identifier", 4),
+                ("Let", 2),
+                ("LineCommentOpen", 1),
+                ("SemiColon", 2),
+            ],
+            stats
+                .kinds()
+                .iter()
+                .map(|(k, v)| (k.as_str(), *v))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![("a", 2), ("b", 1), ("c", 1)],
+            stats
+                .identifiers()
+                .iter()
+                .map(|(k, v)| (k.as_str(), *v))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[rstest]
+    #[case("", 0, 0.0)]
+    #[case("a;", 1, 2.0)]
+    #[case("let a = 1; b", 2, 3.0)] // without a trailing ;
+    #[case("a; /* comment */ ;", 2, 1.5)]
+    fn statements(#[case] source: &str, #[case] count: usize, #[case] average: f64) {
+        let stats = stats(source);
+        assert_eq!(count, stats.statements());
+        assert_eq!(average, stats.average_statement_length());
+    }
+
+    #[rstest]
+    #[case("a", 0)]
+    #[case("f(a[1])", 2)]
+    #[case("{ (1) } [2]", 2)]
+    #[case(r#"{ "${ (a) }" }"#, 3)]
+    #[case(")) (", 1)] // unbalanced
+    fn nesting(#[case] source: &str, #[case] expected: usize) {
+        assert_eq!(expected, stats(source).max_nesting());
+    }
+}
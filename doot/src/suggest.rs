@@ -0,0 +1,58 @@
+// levenshtein distance in chars
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_ch) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_ch) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_ch != *b_ch);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+// the closest candidate, if close enough to be a typo of name
+pub fn did_you_mean<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Option<&'a str> {
+    let max_distance = (name.chars().count() / 3).max(1);
+    candidates
+        .into_iter()
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::{did_you_mean, edit_distance};
+
+    #[rstest]
+    #[case("", "", 0)]
+    #[case("", "abc", 3)]
+    #[case("whle", "while", 1)]
+    #[case("retrun", "return", 2)]
+    #[case("kitten", "sitting", 3)]
+    #[case("é", "e", 1)] // in chars
+    fn distances(#[case] a: &str, #[case] b: &str, #[case] expected: usize) {
+        assert_eq!(expected, edit_distance(a, b));
+        assert_eq!(expected, edit_distance(b, a));
+    }
+
+    #[rstest]
+    #[case("whle", Some("while"))]
+    #[case("fo", Some("for"))]
+    #[case("reserved_words", Some("reserved_word"))]
+    #[case("lett", Some("let"))] // first of the closest
+    #[case("xyz", None)]
+    fn suggestions(#[case] name: &str, #[case] expected: Option<&str>) {
+        let candidates = ["let", "var", "while", "for", "reserved_word", "lets"];
+        assert_eq!(expected, did_you_mean(name, candidates));
+    }
+}
@@ -0,0 +1,84 @@
+use std::collections::BTreeMap;
+
+use crate::Source;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VfsFile {
+    name: String,
+    text: String,
+}
+
+impl VfsFile {
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+impl Source for VfsFile {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn chars(&self) -> impl Iterator<Item = char> + Send {
+        self.text.chars()
+    }
+}
+
+// in memory files for tests and embedders, by name
+#[derive(Debug, Clone, Default)]
+pub struct VfsSource {
+    files: BTreeMap<String, VfsFile>,
+}
+
+impl VfsSource {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // replaces any file with the same name
+    pub fn add(&mut self, name: &str, text: &str) -> &VfsFile {
+        let file = VfsFile {
+            name: name.to_string(),
+            text: text.to_string(),
+        };
+        self.files.insert(name.to_string(), file);
+        &self.files[name]
+    }
+
+    pub fn get(&self, name: &str) -> Option<&VfsFile> {
+        self.files.get(name)
+    }
+
+    pub fn files(&self) -> impl Iterator<Item = &VfsFile> {
+        self.files.values()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use crate::{
+        Source,
+        lexer::{Lexer, tokens::Token},
+    };
+
+    use super::VfsSource;
+
+    #[rstest]
+    fn files() {
+        let mut vfs = VfsSource::new();
+        vfs.add("main.doot", "let a = 1;");
+        vfs.add("lib.doot", "fn f() {}");
+        vfs.add("main.doot", "a");
+        assert_eq!(
+            vec!["lib.doot", "main.doot"],
+            vfs.files().map(|f| f.name()).collect::<Vec<_>>()
+        );
+        let main = vfs.get("main.doot").unwrap();
+        assert_eq!("a", main.text());
+        let tokens: Vec<_> = Lexer::new(main.chars()).map(Result::unwrap).collect();
+        assert_eq!(vec![Token::Identifier("a".to_string()), Token::Eof], tokens);
+        assert!(vfs.get("other.doot").is_none());
+    }
+}
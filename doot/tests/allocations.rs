@@ -0,0 +1,60 @@
+// a separate test binary, so that the counting allocator only sees the lexer
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use doot::lexer::Lexer;
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn allocations(source: &str) -> usize {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    for token in Lexer::new(source.chars()) {
+        token.unwrap();
+    }
+    ALLOCATIONS.load(Ordering::Relaxed) - before
+}
+
+// a single test, other tests running in parallel would be counted too
+#[test]
+fn allocations_per_token() {
+    let identifiers = |count: usize, name: &str, separator: &str| -> usize {
+        allocations(&format!("{}{}", name, separator).repeat(count))
+    };
+    // the same for every token
+    let per_token = identifiers(200, "a", " ") - identifiers(100, "a", " ");
+    assert_eq!(
+        per_token,
+        identifiers(300, "a", " ") - identifiers(200, "a", " ")
+    );
+    // skipped characters are not buffered
+    assert_eq!(
+        identifiers(100, "a", " "),
+        identifiers(100, "a", "          ")
+    );
+    // the buffer is reused, longer tokens only grow it a few times
+    assert!(identifiers(100, "abcdefghijklmnop", " ") < identifiers(100, "a", " ") + 10);
+}
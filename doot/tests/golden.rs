@@ -0,0 +1,52 @@
+// lexes every .dt file in tests/fixtures and compares the tokens with the .tokens file next to it,
+// run with DOOT_BLESS=1 to write the .tokens files instead
+
+use std::{env, fs, path::Path};
+
+use doot::lexer::Lexer;
+
+fn dump(source: &str) -> String {
+    let mut lexer = Lexer::new(source.chars());
+    let mut lines = vec![];
+    while let Some(token) = lexer.next() {
+        let span = lexer.span().range();
+        lines.push(match token {
+            Ok(token) => format!("{:?} {:?}", span, token),
+            Err(err) => format!("{:?} error: {}", err.span().unwrap_or(span), err),
+        });
+    }
+    lines.join("\n") + "\n"
+}
+
+#[test]
+fn fixtures() {
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let bless = env::var_os("DOOT_BLESS").is_some();
+    let mut paths: Vec<_> = fs::read_dir(&fixtures)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "dt"))
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty());
+    let mut failed = vec![];
+    for path in paths {
+        let actual = dump(&fs::read_to_string(&path).unwrap());
+        let expected_path = path.with_extension("tokens");
+        if bless {
+            fs::write(&expected_path, actual).unwrap();
+            continue;
+        }
+        let expected = fs::read_to_string(&expected_path).unwrap_or_default();
+        if actual != expected {
+            eprintln!(
+                "{} does not match {}:\n{}",
+                path.display(),
+                expected_path.display(),
+                actual
+            );
+            failed.push(path);
+        }
+    }
+    assert!(failed.is_empty(), "mismatched fixtures: {:?}", failed);
+}